@@ -0,0 +1,43 @@
+#![allow(clippy::needless_return)]
+
+use std::time::{Duration, Instant};
+use lang3::incremental::LexedFile;
+use lang3::source::{SourceFile, Span};
+
+const ITERATIONS: u32 = 1000;
+
+fn source() -> String {
+    let function = "fn compute(a, b) {\n    // sum both\n    let total = a + b * 2;\n    print(\"total\", total);\n    return total;\n}\n\n";
+    return function.repeat(5000);
+}
+
+fn report(name: &str, elapsed: Duration, iterations: u32) {
+    println!("{:<24} {:>10.3} ms", name, elapsed.as_secs_f64() * 1000.0 / iterations as f64);
+}
+
+fn main() {
+    let text = source();
+    println!("{} bytes", text.len());
+
+    let start = Instant::now();
+    let mut file = LexedFile::new(SourceFile::anonymous(text.clone()));
+    report("full lex", start.elapsed(), 1);
+    println!("{} tokens", file.tokens().len());
+
+    let middle = text.len() / 2;
+    let offset = middle + text[middle..].find("total").unwrap();
+
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        file.apply_edit(Span::new(offset, offset), "x");
+        file.apply_edit(Span::new(offset, offset + 1), "");
+    }
+    report("identifier edit", start.elapsed(), ITERATIONS * 2);
+
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        file.apply_edit(Span::new(offset, offset), "\n");
+        file.apply_edit(Span::new(offset, offset + 1), "");
+    }
+    report("line break edit", start.elapsed(), ITERATIONS * 2);
+}
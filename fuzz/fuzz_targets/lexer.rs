@@ -0,0 +1,26 @@
+#![no_main]
+
+//! Lexes arbitrary input the way `lang3 check` does, recovering after
+//! errors. Run with `cargo +nightly fuzz run lexer`.
+
+use libfuzzer_sys::fuzz_target;
+use lang3::lexer::Lexer;
+
+fuzz_target!(|data: &[u8]| {
+    let code = String::from_utf8_lossy(data).into_owned();
+    let mut lexer = Lexer::new(&code).with_trivia();
+    let mut position = lexer.position();
+    let mut end = 0;
+
+    while let Some(res) = lexer.next_token() {
+        if let Ok(token) = res {
+            assert!(end <= token.span.start && token.span.start <= token.span.end);
+            assert!(token.span.end <= code.len() && code.is_char_boundary(token.span.end));
+            end = token.span.end;
+        } else if lexer.position() == position {
+            break;
+        }
+
+        position = lexer.position();
+    }
+});
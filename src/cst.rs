@@ -0,0 +1,278 @@
+use std::rc::Rc;
+use crate::lexer::{Lexer, LexerError};
+use crate::source::{SourceFile, Span};
+use crate::token::{Token, TokenKind};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TokenId(usize);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CstKind {
+    Root,
+    Parenthesized,
+    Bracketed,
+    Block,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CstChild {
+    Node(NodeId),
+    Token(TokenId),
+}
+
+#[derive(Debug)]
+pub struct CstNode {
+    pub kind: CstKind,
+    pub parent: Option<NodeId>,
+    pub children: Vec<CstChild>,
+    pub span: Span,
+}
+
+/// Lossless concrete syntax tree. Every token of the file, whitespace and
+/// comments included, is a child of exactly one node, so concatenating the
+/// leaves in order gives back the source text.
+///
+/// Until the language has a parser the tree only captures delimiter
+/// nesting: each `(...)`, `[...]` and `{...}` group is a node holding its
+/// delimiters and everything between them.
+#[derive(Debug)]
+pub struct Cst {
+    file: Rc<SourceFile>,
+    tokens: Vec<Token>,
+    token_parents: Vec<NodeId>,
+    nodes: Vec<CstNode>,
+}
+
+impl Cst {
+    pub fn parse(file: &Rc<SourceFile>) -> Result<Self, LexerError> {
+        let mut lexer = Lexer::from_source_file(file).with_trivia();
+        let mut cst = Cst {
+            file: Rc::clone(file),
+            tokens: Vec::new(),
+            token_parents: Vec::new(),
+            nodes: Vec::new(),
+        };
+
+        let root = cst.add_node(CstKind::Root, None, 0);
+        let mut current = root;
+
+        while let Some(token) = lexer.next_token() {
+            let token = token?;
+            let kind = token.kind;
+            let offset = token.span.start;
+
+            if let Some(group) = Cst::opened_group(kind) {
+                current = cst.add_node(group, Some(current), offset);
+                cst.add_token(current, token);
+                continue;
+            }
+
+            // a closer that doesn't match the innermost group is kept as a
+            // stray token instead of failing the whole tree
+            if Cst::closed_group(kind).is_some_and(|group| group == cst.nodes[current.0].kind) {
+                cst.add_token(current, token);
+                cst.nodes[current.0].span.end = cst.last_token_end();
+                current = cst.nodes[current.0].parent.unwrap();
+                continue;
+            }
+
+            cst.add_token(current, token);
+        }
+
+        // groups still open at the end of the file end with it
+        let end = cst.file.text.len();
+        while current != root {
+            cst.nodes[current.0].span.end = end;
+            current = cst.nodes[current.0].parent.unwrap();
+        }
+        cst.nodes[root.0].span.end = end;
+
+        return Ok(cst);
+    }
+
+    pub fn file(&self) -> &Rc<SourceFile> {
+        return &self.file;
+    }
+
+    pub fn root(&self) -> NodeId {
+        return NodeId(0);
+    }
+
+    pub fn node(&self, id: NodeId) -> &CstNode {
+        return &self.nodes[id.0];
+    }
+
+    pub fn token(&self, id: TokenId) -> &Token {
+        return &self.tokens[id.0];
+    }
+
+    pub fn token_parent(&self, id: TokenId) -> NodeId {
+        return self.token_parents[id.0];
+    }
+
+    pub fn tokens(&self) -> &[Token] {
+        return &self.tokens;
+    }
+
+    /// Source text of a node, delimiters and trivia included.
+    pub fn text(&self, id: NodeId) -> &str {
+        return self.file.slice(self.node(id).span);
+    }
+
+    /// Innermost node whose span contains the byte `offset`.
+    pub fn node_at(&self, offset: usize) -> NodeId {
+        let mut current = self.root();
+
+        'descend: loop {
+            for child in &self.node(current).children {
+                if let CstChild::Node(id) = child {
+                    let span = self.node(*id).span;
+                    if span.start <= offset && offset < span.end {
+                        current = *id;
+                        continue 'descend;
+                    }
+                }
+            }
+
+            return current;
+        }
+    }
+
+    /// Token containing the byte `offset`, trivia included.
+    pub fn token_at(&self, offset: usize) -> Option<TokenId> {
+        let index = self.tokens.partition_point(|t| t.span.end <= offset);
+
+        if index < self.tokens.len() && self.tokens[index].span.start <= offset {
+            return Some(TokenId(index));
+        }
+
+        return None;
+    }
+
+    fn add_node(&mut self, kind: CstKind, parent: Option<NodeId>, start: usize) -> NodeId {
+        let id = NodeId(self.nodes.len());
+
+        self.nodes.push(CstNode {
+            kind,
+            parent,
+            children: Vec::new(),
+            span: Span::new(start, start),
+        });
+
+        if let Some(parent) = parent {
+            self.nodes[parent.0].children.push(CstChild::Node(id));
+        }
+
+        return id;
+    }
+
+    fn add_token(&mut self, node: NodeId, token: Token) {
+        let id = TokenId(self.tokens.len());
+        self.tokens.push(token);
+        self.token_parents.push(node);
+        self.nodes[node.0].children.push(CstChild::Token(id));
+    }
+
+    fn last_token_end(&self) -> usize {
+        return self.tokens.last().map_or(0, |t| t.span.end);
+    }
+
+    fn opened_group(kind: TokenKind) -> Option<CstKind> {
+        return match kind {
+            TokenKind::LeftParenthesis => Some(CstKind::Parenthesized),
+            TokenKind::LeftBracket => Some(CstKind::Bracketed),
+            TokenKind::LeftBrace => Some(CstKind::Block),
+            _ => None,
+        };
+    }
+
+    fn closed_group(kind: TokenKind) -> Option<CstKind> {
+        return match kind {
+            TokenKind::RightParenthesis => Some(CstKind::Parenthesized),
+            TokenKind::RightBracket => Some(CstKind::Bracketed),
+            TokenKind::RightBrace => Some(CstKind::Block),
+            _ => None,
+        };
+    }
+}
+
+#[cfg(test)]
+mod cst_tests {
+    use std::path::PathBuf;
+    use std::rc::Rc;
+    use crate::source::{SourceFile, SourceMap};
+    use super::{Cst, CstChild, CstKind};
+
+    fn file(code: &str) -> Rc<SourceFile> {
+        let mut map = SourceMap::new();
+        let id = map.add_file(PathBuf::from("test.l3"), code.to_string());
+        return Rc::clone(map.get(id));
+    }
+
+    fn leaves(cst: &Cst, node: super::NodeId, out: &mut String) {
+        for child in &cst.node(node).children {
+            match child {
+                CstChild::Node(id) => leaves(cst, *id, out),
+                CstChild::Token(id) => out.push_str(cst.file().slice(cst.token(*id).span)),
+            }
+        }
+    }
+
+    #[test]
+    fn test_cst_is_lossless() {
+        // given
+        let code = "fn main() {\n    // comment\n    print(a[1], \"s\");\n}\n";
+
+        // when
+        let cst = Cst::parse(&file(code)).unwrap();
+
+        // then
+        let mut text = String::new();
+        leaves(&cst, cst.root(), &mut text);
+        assert_eq!(text, code);
+        assert_eq!(cst.text(cst.root()), code);
+    }
+
+    #[test]
+    fn test_cst_nesting() {
+        // given
+        let code = "f(a[1]) { x }";
+
+        // when
+        let cst = Cst::parse(&file(code)).unwrap();
+
+        // then
+        let bracket = cst.node_at(code.find('1').unwrap());
+        assert_eq!(cst.node(bracket).kind, CstKind::Bracketed);
+        assert_eq!(cst.text(bracket), "[1]");
+
+        let parens = cst.node(bracket).parent.unwrap();
+        assert_eq!(cst.node(parens).kind, CstKind::Parenthesized);
+        assert_eq!(cst.node(parens).parent, Some(cst.root()));
+
+        let block = cst.node_at(code.find('x').unwrap());
+        assert_eq!(cst.text(block), "{ x }");
+
+        let token = cst.token_at(code.find('x').unwrap()).unwrap();
+        assert_eq!(cst.token(token).lexeme, "x");
+        assert_eq!(cst.token_parent(token), block);
+    }
+
+    #[test]
+    fn test_cst_unbalanced() {
+        // given
+        let code = "{ ( ] ";
+
+        // when
+        let cst = Cst::parse(&file(code)).unwrap();
+
+        // then
+        let parens = cst.node_at(code.find(']').unwrap());
+        assert_eq!(cst.node(parens).kind, CstKind::Parenthesized);
+        assert_eq!(cst.node(parens).span.end, code.len());
+        assert_eq!(cst.text(cst.root()), code);
+    }
+}
@@ -0,0 +1,333 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use crate::manifest::{Manifest, ManifestError, MANIFEST_NAME, SOURCE_EXTENSION};
+
+pub const LOCKFILE_NAME: &str = "lang3.lock";
+/// Environment variable listing extra module directories, separated like
+/// `PATH`.
+pub const MODULE_PATH_VAR: &str = "LANG3_PATH";
+
+/// A dependency after resolution, with its manifest loaded from its
+/// canonical location.
+#[derive(Debug, Clone)]
+pub struct ResolvedPackage {
+    pub name: String,
+    pub manifest: Manifest,
+}
+
+/// Resolves the dependencies of `project` and, transitively, theirs.
+/// Packages are identified by name; two paths for the same name are an
+/// error. The result is sorted by name.
+pub fn resolve(project: &Manifest) -> Result<Vec<ResolvedPackage>, ManifestError> {
+    let mut resolved: BTreeMap<String, ResolvedPackage> = BTreeMap::new();
+    let mut pending = vec![project.clone()];
+
+    while let Some(manifest) = pending.pop() {
+        for (name, dependency) in &manifest.dependencies {
+            let manifest_path = manifest.root.join(MANIFEST_NAME);
+            let root = manifest.root.join(&dependency.path).canonicalize()
+                .map_err(|err| ManifestError::new(&manifest_path, format!(
+                    "dependency '{}' not found at '{}': {}", name, dependency.path.display(), err)))?;
+
+            if let Some(existing) = resolved.get(name) {
+                if existing.manifest.root != root {
+                    return Err(ManifestError::new(&manifest_path, format!(
+                        "dependency '{}' resolves to both '{}' and '{}'",
+                        name, existing.manifest.root.display(), root.display())));
+                }
+                continue;
+            }
+
+            let dependency = Manifest::load(&root.join(MANIFEST_NAME))?;
+            if dependency.package.name != *name {
+                return Err(ManifestError::new(&manifest_path, format!(
+                    "dependency '{}' points at package '{}'", name, dependency.package.name)));
+            }
+
+            pending.push(dependency.clone());
+            resolved.insert(name.clone(), ResolvedPackage { name: name.clone(), manifest: dependency });
+        }
+    }
+
+    return Ok(resolved.into_values().collect());
+}
+
+/// Resolves the dependencies like `resolve` and checks them against
+/// `lang3.lock`, rewriting it when they differ, e.g. after the manifest was
+/// edited by hand. Returns the packages and whether the lockfile changed.
+/// A project without dependencies needs no lockfile.
+pub fn resolve_locked(project: &Manifest) -> Result<(Vec<ResolvedPackage>, bool), ManifestError> {
+    let packages = resolve(project)?;
+    let lockfile = Lockfile::new(project, &packages);
+
+    if Lockfile::load(project).unwrap_or_default() == lockfile {
+        return Ok((packages, false));
+    }
+
+    lockfile.write(project)
+        .map_err(|err| ManifestError::new(&project.root.join(LOCKFILE_NAME), err))?;
+
+    return Ok((packages, true));
+}
+
+/// Finds the file an import path such as `vendorlib::thing` refers to.
+/// A first segment naming a dependency looks the rest up in that package,
+/// anything else is looked up in the project's own sources, then its
+/// module paths and then `search_paths`, usually `env_module_paths()`.
+pub fn resolve_module(project: &Manifest, packages: &[ResolvedPackage], search_paths: &[PathBuf], path: &str) -> Option<PathBuf> {
+    return module_candidates(project, packages, search_paths, path).into_iter()
+        .find(|file| file.is_file());
+}
+
+/// Every file `resolve_module` tries for an import path, in order, to
+/// explain why a module was or wasn't found.
+pub fn module_candidates(project: &Manifest, packages: &[ResolvedPackage], search_paths: &[PathBuf], path: &str) -> Vec<PathBuf> {
+    let segments: Vec<&str> = path.split("::").collect();
+
+    let (roots, segments) = match packages.iter().find(|p| p.name == segments[0]) {
+        Some(package) if segments.len() > 1 => (package.manifest.source_roots(), &segments[1..]),
+        _ => ([project.source_roots(), project.module_paths(), search_paths.to_vec()].concat(), &segments[..]),
+    };
+
+    let mut relative: PathBuf = segments.iter().collect();
+    relative.set_extension(SOURCE_EXTENSION);
+
+    return roots.iter()
+        .map(|root| root.join(&relative))
+        .collect();
+}
+
+/// The directories in `LANG3_PATH`, skipping empty entries. Read once by
+/// the command line and passed on as the search paths.
+pub fn env_module_paths() -> Vec<PathBuf> {
+    let Some(paths) = env::var_os(MODULE_PATH_VAR) else { return Vec::new() };
+
+    return env::split_paths(&paths)
+        .filter(|p| !p.as_os_str().is_empty())
+        .collect();
+}
+
+/// Contents of `lang3.lock`: the resolved packages with their paths
+/// relative to the project root, so checkouts in different places lock
+/// to the same file.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Lockfile {
+    #[serde(default, rename = "package")]
+    pub packages: Vec<LockedPackage>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct LockedPackage {
+    pub name: String,
+    pub path: String,
+}
+
+impl Lockfile {
+    pub fn new(project: &Manifest, packages: &[ResolvedPackage]) -> Self {
+        let root = project.root.canonicalize().unwrap_or_else(|_| project.root.clone());
+        let packages = packages.iter()
+            .map(|p| LockedPackage {
+                name: p.name.clone(),
+                path: relative_path(&root, &p.manifest.root).to_string_lossy().replace('\\', "/"),
+            })
+            .collect();
+
+        return Lockfile { packages };
+    }
+
+    pub fn load(project: &Manifest) -> Option<Self> {
+        let text = fs::read_to_string(project.root.join(LOCKFILE_NAME)).ok()?;
+        return toml::from_str(&text).ok();
+    }
+
+    pub fn write(&self, project: &Manifest) -> std::io::Result<()> {
+        let body = toml::to_string(self)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+        let text = format!("# This file is generated by lang3, do not edit it by hand.\n\n{}", body);
+        return fs::write(project.root.join(LOCKFILE_NAME), text);
+    }
+}
+
+/// Adds a path dependency to the project manifest, keeping the rest of the
+/// file as it was, and rewrites the lockfile.
+pub fn add_dependency(project: &Manifest, name: &str, path: &Path) -> Result<Manifest, ManifestError> {
+    let manifest_path = project.root.join(MANIFEST_NAME);
+
+    let dependency = Manifest::load(&path.join(MANIFEST_NAME))?;
+    if dependency.package.name != name {
+        return Err(ManifestError::new(&manifest_path, format!(
+            "'{}' holds package '{}', not '{}'", path.display(), dependency.package.name, name)));
+    }
+
+    let root = project.root.canonicalize().map_err(|err| ManifestError::new(&manifest_path, err))?;
+    let target = path.canonicalize().map_err(|err| ManifestError::new(&manifest_path, err))?;
+    let relative = relative_path(&root, &target).to_string_lossy().replace('\\', "/");
+
+    let text = fs::read_to_string(&manifest_path).map_err(|err| ManifestError::new(&manifest_path, err))?;
+    let mut document: toml_edit::DocumentMut = text.parse()
+        .map_err(|err| ManifestError::new(&manifest_path, err))?;
+
+    let mut entry = toml_edit::InlineTable::new();
+    entry.insert("path", relative.into());
+    let dependencies = document.entry("dependencies")
+        .or_insert(toml_edit::table())
+        .as_table_mut()
+        .ok_or_else(|| ManifestError::new(&manifest_path, "'dependencies' is not a table"))?;
+    dependencies.insert(name, toml_edit::value(entry));
+
+    let updated = Manifest::parse(&document.to_string(), &project.root)?;
+    let packages = resolve(&updated)?;
+
+    fs::write(&manifest_path, document.to_string()).map_err(|err| ManifestError::new(&manifest_path, err))?;
+    Lockfile::new(&updated, &packages).write(&updated)
+        .map_err(|err| ManifestError::new(&updated.root.join(LOCKFILE_NAME), err))?;
+
+    return Ok(updated);
+}
+
+/// Path from `from` to `to`, both absolute and canonical.
+fn relative_path(from: &Path, to: &Path) -> PathBuf {
+    let from: Vec<Component> = from.components().collect();
+    let to: Vec<Component> = to.components().collect();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+
+    let mut path = PathBuf::new();
+    for _ in common..from.len() {
+        path.push("..");
+    }
+    for component in &to[common..] {
+        path.push(component);
+    }
+
+    if path.as_os_str().is_empty() {
+        path.push(".");
+    }
+
+    return path;
+}
+
+#[cfg(test)]
+mod dependencies_tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use crate::manifest::{Manifest, MANIFEST_NAME};
+    use super::{add_dependency, module_candidates, relative_path, resolve, resolve_locked, resolve_module, Lockfile, LOCKFILE_NAME};
+
+    fn package(dir: &Path, manifest: &str) {
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join(MANIFEST_NAME), manifest).unwrap();
+    }
+
+    #[test]
+    fn test_relative_path() {
+        // given
+        let cases = [
+            ("/a/b/app", "/a/vendor/lib", "../../vendor/lib"),
+            ("/a", "/a/lib", "lib"),
+            ("/a", "/a", "."),
+        ];
+
+        for (from, to, expected) in cases {
+            // when
+            let path = relative_path(Path::new(from), Path::new(to));
+
+            // then
+            assert_eq!(path, PathBuf::from(expected));
+        }
+    }
+
+    #[test]
+    fn test_add_and_resolve() {
+        // given
+        let dir = std::env::temp_dir().join(format!("lang3_dependencies_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let dir = dir.canonicalize().unwrap();
+        package(&dir.join("app"), "[package]\nname = \"app\" # the app\nsource-dirs = [\"src\"]\n");
+        package(&dir.join("vendor/util"), "[package]\nname = \"util\"\nsource-dirs = [\"src\"]\n");
+        package(&dir.join("vendor/text"), "[package]\nname = \"text\"\nsource-dirs = [\"src\"]\n\n[dependencies]\nutil = { path = \"../util\" }\n");
+        fs::write(dir.join("vendor/text/src/format.l3"), "").unwrap();
+        fs::write(dir.join("app/src/local.l3"), "").unwrap();
+        let project = Manifest::load(&dir.join("app").join(MANIFEST_NAME)).unwrap();
+
+        // when
+        let updated = add_dependency(&project, "text", &dir.join("vendor/text")).unwrap();
+        let packages = resolve(&updated).unwrap();
+        let manifest_text = fs::read_to_string(dir.join("app").join(MANIFEST_NAME)).unwrap();
+        let lockfile = Lockfile::load(&updated).unwrap();
+        let module = resolve_module(&updated, &packages, &[], "text::format");
+        let local = resolve_module(&updated, &packages, &[], "local");
+        let missing = resolve_module(&updated, &packages, &[], "text::missing");
+        let wrong_name = add_dependency(&project, "other", &dir.join("vendor/util"));
+        fs::remove_dir_all(&dir).unwrap();
+
+        // then
+        assert!(manifest_text.contains("name = \"app\" # the app"));
+        assert!(manifest_text.contains("text = { path = \"../vendor/text\" }"));
+
+        let names: Vec<&str> = packages.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["text", "util"]);
+
+        let locked: Vec<(&str, &str)> = lockfile.packages.iter().map(|p| (p.name.as_str(), p.path.as_str())).collect();
+        assert_eq!(locked, [("text", "../vendor/text"), ("util", "../vendor/util")]);
+
+        assert_eq!(module, Some(dir.join("vendor/text/src/format.l3")));
+        assert_eq!(local, Some(dir.join("app/src/local.l3")));
+        assert_eq!(missing, None);
+        assert!(wrong_name.unwrap_err().to_string().contains("holds package 'util'"));
+    }
+
+    #[test]
+    fn test_stale_lockfile_is_refreshed() {
+        // given
+        let dir = std::env::temp_dir().join(format!("lang3_stale_lock_{}", std::process::id()));
+        package(&dir.join("app"), "[package]\nname = \"app\"\n\n[dependencies]\nutil = { path = \"../util\" }\n");
+        package(&dir.join("util"), "[package]\nname = \"util\"\n");
+        package(&dir.join("empty"), "[package]\nname = \"empty\"\n");
+        fs::write(dir.join("app").join(LOCKFILE_NAME), "[[package]]\nname = \"old\"\npath = \"../old\"\n").unwrap();
+        let project = Manifest::load(&dir.join("app").join(MANIFEST_NAME)).unwrap();
+        let empty = Manifest::load(&dir.join("empty").join(MANIFEST_NAME)).unwrap();
+
+        // when
+        let (packages, refreshed) = resolve_locked(&project).unwrap();
+        let lockfile = Lockfile::load(&project).unwrap();
+        let (_, refreshed_again) = resolve_locked(&project).unwrap();
+        let (_, empty_refreshed) = resolve_locked(&empty).unwrap();
+        let empty_has_lockfile = dir.join("empty").join(LOCKFILE_NAME).exists();
+        fs::remove_dir_all(&dir).unwrap();
+
+        // then
+        assert_eq!(packages.len(), 1);
+        assert!(refreshed);
+        assert_eq!(lockfile, Lockfile::new(&project, &packages));
+        assert!(!refreshed_again);
+        assert!(!empty_refreshed && !empty_has_lockfile);
+    }
+
+    #[test]
+    fn test_module_search_path() {
+        // given
+        let dir = std::env::temp_dir().join(format!("lang3_module_path_{}", std::process::id()));
+        package(&dir.join("app"), "[package]\nname = \"app\"\nsource-dirs = [\"src\"]\nmodule-paths = [\"lib\"]\n");
+        fs::create_dir_all(dir.join("global/net")).unwrap();
+        fs::write(dir.join("global/net/http.l3"), "").unwrap();
+        let project = Manifest::load(&dir.join("app").join(MANIFEST_NAME)).unwrap();
+        let search_paths = [dir.join("missing"), dir.join("global")];
+
+        // when
+        let candidates = module_candidates(&project, &[], &search_paths, "net::http");
+        let module = resolve_module(&project, &[], &search_paths, "net::http");
+        fs::remove_dir_all(&dir).unwrap();
+
+        // then
+        assert_eq!(candidates, [
+            dir.join("app/src/net/http.l3"),
+            dir.join("app/lib/net/http.l3"),
+            dir.join("missing/net/http.l3"),
+            dir.join("global/net/http.l3"),
+        ]);
+        assert_eq!(module, Some(dir.join("global/net/http.l3")));
+    }
+}
@@ -0,0 +1,357 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::rc::Rc;
+use colored::Colorize;
+use crate::cst::Cst;
+use crate::lexer::{Lexer, LexerError};
+use crate::lints::{run_lints, LINT_PASSES};
+use crate::source::{SourceCodeLocation, SourceFile, Span, Spanned};
+use crate::token::{Token, TokenKind};
+use crate::util::{print_file_location, print_location};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Level {
+    Allow,
+    Warn,
+    Deny,
+}
+
+impl Level {
+    pub fn from_name(name: &str) -> Option<Self> {
+        return match name {
+            "allow" => Some(Level::Allow),
+            "warn" => Some(Level::Warn),
+            "deny" => Some(Level::Deny),
+            _ => None,
+        };
+    }
+}
+
+/// A named check whose findings can be allowed, reported as warnings or
+/// turned into errors.
+#[derive(Debug)]
+pub struct Lint {
+    pub name: &'static str,
+    pub default: Level,
+    pub description: &'static str,
+}
+
+pub const UNKNOWN_LINT: Lint = Lint {
+    name: "unknown_lint",
+    default: Level::Warn,
+    description: "an annotation or flag names a lint that doesn't exist",
+};
+
+/// Every known lint: the ones reported here plus the registered passes.
+pub fn lints() -> impl Iterator<Item = &'static Lint> {
+    return std::iter::once(&UNKNOWN_LINT).chain(LINT_PASSES.iter().map(|p| p.lint));
+}
+
+pub fn find_lint(name: &str) -> Option<&'static Lint> {
+    return lints().find(|l| l.name == name);
+}
+
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub lint: Option<&'static str>,
+    pub message: String,
+    pub location: Option<SourceCodeLocation>,
+}
+
+impl From<LexerError> for Diagnostic {
+    fn from(err: LexerError) -> Self {
+        return Diagnostic {
+            severity: Severity::Error,
+            lint: None,
+            message: err.message().to_string(),
+            location: err.location().cloned(),
+        };
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if let Some(location) = &self.location {
+            print_file_location(location);
+            print_location(&location.file, location.line, location.start_char, location.end_char);
+        }
+
+        let severity = match self.severity {
+            Severity::Error => "error".bright_red().bold(),
+            Severity::Warning => "warning".yellow().bold(),
+        };
+
+        return match self.lint {
+            Some(lint) => write!(f, "{}[{}]: {}", severity, lint, self.message),
+            None => write!(f, "{}: {}", severity, self.message),
+        };
+    }
+}
+
+/// Lint levels given on the command line, `-A`, `-W` and `-D`, or in the
+/// manifest.
+#[derive(Debug, Default, Clone)]
+pub struct LintConfig {
+    levels: HashMap<&'static str, Level>,
+    /// Report every warning as an error.
+    pub deny_warnings: bool,
+}
+
+impl LintConfig {
+    pub fn set(&mut self, name: &str, level: Level) -> Result<(), String> {
+        let lint = find_lint(name).ok_or_else(|| format!("Unknown lint '{}'", name))?;
+        self.levels.insert(lint.name, level);
+        return Ok(());
+    }
+
+    /// Turns on the lints that are off by default, except those given a
+    /// level explicitly. This is `lang3 check --lint`.
+    pub fn enable_all(&mut self) {
+        for lint in lints().filter(|l| l.default == Level::Allow) {
+            self.levels.entry(lint.name).or_insert(Level::Warn);
+        }
+    }
+
+    pub fn level(&self, lint: &Lint) -> Level {
+        return self.levels.get(lint.name).copied().unwrap_or(lint.default);
+    }
+}
+
+/// Lint levels set in the source. `@allow(name, ...)`, `@warn(...)` and
+/// `@deny(...)` apply to the statement that follows them, `@!allow(...)`
+/// and friends to the whole file. Both take precedence over the command
+/// line.
+#[derive(Debug, Default)]
+pub struct Annotations {
+    file: HashMap<&'static str, Level>,
+    statements: Vec<Spanned<(&'static str, Level)>>,
+}
+
+impl Annotations {
+    /// Level for a finding of `lint` at `span`, from the innermost
+    /// statement annotation covering it or else the file annotations.
+    fn level(&self, lint: &Lint, span: Span) -> Option<Level> {
+        return self.statements.iter()
+            .filter(|s| s.node.0 == lint.name && s.span.contains(span))
+            .min_by_key(|s| s.span.len())
+            .map(|s| s.node.1)
+            .or_else(|| self.file.get(lint.name).copied());
+    }
+}
+
+/// Collects the diagnostics of a check run and decides, in one place, what
+/// becomes of each lint finding: dropped, a warning or an error.
+pub struct Diagnostics {
+    config: LintConfig,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Diagnostics {
+    pub fn new(config: LintConfig) -> Self {
+        return Diagnostics { config, diagnostics: Vec::new() };
+    }
+
+    pub fn error(&mut self, diagnostic: Diagnostic) {
+        self.diagnostics.push(Diagnostic { severity: Severity::Error, ..diagnostic });
+    }
+
+    pub fn lint(&mut self, lint: &'static Lint, annotations: &Annotations, span: Span, message: String, location: SourceCodeLocation) {
+        let level = annotations.level(lint, span).unwrap_or(self.config.level(lint));
+        let severity = match level {
+            Level::Allow => return,
+            Level::Warn if !self.config.deny_warnings => Severity::Warning,
+            Level::Warn | Level::Deny => Severity::Error,
+        };
+
+        self.diagnostics.push(Diagnostic {
+            severity,
+            lint: Some(lint.name),
+            message,
+            location: Some(location),
+        });
+    }
+
+    /// Reads the lint annotations of a file, reporting names that aren't
+    /// lints. Trivia in `tokens` is skipped.
+    pub fn annotations(&mut self, file: &Rc<SourceFile>, tokens: &[Token]) -> Annotations {
+        let tokens: Vec<&Token> = tokens.iter().filter(|t| !t.kind.is_trivia()).collect();
+        let kind_at = |i: usize| tokens.get(i).map(|t| t.kind);
+        let mut annotations = Annotations::default();
+        let mut unknown = Vec::new();
+
+        for (i, at) in tokens.iter().enumerate() {
+            if at.kind != TokenKind::At {
+                continue;
+            }
+
+            let whole_file = kind_at(i + 1) == Some(TokenKind::Bang);
+            let name = if whole_file { i + 2 } else { i + 1 };
+            let level = match tokens.get(name).and_then(|t| Level::from_name(&t.lexeme)) {
+                Some(level) if kind_at(name + 1) == Some(TokenKind::LeftParenthesis) => level,
+                _ => continue,
+            };
+
+            let mut close = name + 2;
+            let mut lints = Vec::new();
+            while let Some(token) = tokens.get(close) {
+                match token.kind {
+                    TokenKind::Identifier => match find_lint(&token.lexeme) {
+                        Some(lint) => lints.push(lint.name),
+                        None => unknown.push(*token),
+                    },
+                    TokenKind::Comma => {},
+                    _ => break,
+                }
+                close += 1;
+            }
+
+            if whole_file {
+                annotations.file.extend(lints.into_iter().map(|l| (l, level)));
+                continue;
+            }
+
+            let end = statement_end(&tokens, close + 1);
+            let span = at.span.join(tokens[end.min(tokens.len() - 1)].span);
+            annotations.statements.extend(lints.into_iter().map(|l| Spanned::new((l, level), span)));
+        }
+
+        for token in unknown {
+            let location = SourceCodeLocation::new(Rc::clone(file), token.line, token.start_char, token.end_char);
+            self.lint(&UNKNOWN_LINT, &annotations, token.span, format!("Unknown lint '{}'", token.lexeme), location);
+        }
+
+        return annotations;
+    }
+
+    /// Lexes a file, recording every error, and runs the lints over it if
+    /// it lexed cleanly. The file's diagnostics are sorted by position.
+    pub fn check_file(&mut self, file: &Rc<SourceFile>) {
+        let first = self.diagnostics.len();
+        let mut lexer = Lexer::from_source_file(file);
+        let mut position = lexer.position();
+        let errors = self.count(Severity::Error);
+
+        while let Some(res) = lexer.next_token() {
+            if let Err(err) = res {
+                self.error(err.into());
+
+                // an error that consumed nothing would repeat forever
+                if lexer.position() == position {
+                    break;
+                }
+            }
+            position = lexer.position();
+        }
+
+        if errors == self.count(Severity::Error) {
+            if let Ok(cst) = Cst::parse(file) {
+                let annotations = self.annotations(file, cst.tokens());
+                run_lints(&cst, &annotations, self);
+            }
+        }
+
+        self.diagnostics[first..].sort_by_key(|d| d.location.as_ref().map(|l| (l.line, l.start_char)));
+    }
+
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        return &self.diagnostics;
+    }
+
+    pub fn count(&self, severity: Severity) -> usize {
+        return self.diagnostics.iter().filter(|d| d.severity == severity).count();
+    }
+}
+
+/// Index of the last token of the statement starting at `start`: its `;`
+/// or the `}` closing its block.
+fn statement_end(tokens: &[&Token], start: usize) -> usize {
+    let mut depth = 0;
+
+    for (i, token) in tokens.iter().enumerate().skip(start) {
+        match token.kind {
+            TokenKind::LeftParenthesis | TokenKind::LeftBracket | TokenKind::LeftBrace => depth += 1,
+            TokenKind::RightParenthesis | TokenKind::RightBracket => depth -= 1,
+            TokenKind::RightBrace => {
+                depth -= 1;
+                if depth <= 0 {
+                    return i;
+                }
+            },
+            TokenKind::Semicolon if depth == 0 => return i,
+            _ => {},
+        }
+    }
+
+    return tokens.len();
+}
+
+#[cfg(test)]
+mod diagnostics_tests {
+    use std::rc::Rc;
+    use crate::incremental::LexedFile;
+    use crate::source::{SourceCodeLocation, SourceFile};
+    use crate::lints::UNUSED_VARIABLE;
+    use super::{Diagnostics, Level, LintConfig, Severity};
+
+    fn lint_each_let(code: &str, config: LintConfig) -> Vec<(usize, Severity)> {
+        let lexed = LexedFile::new(SourceFile::anonymous(code.to_string()));
+        let file = Rc::clone(lexed.file());
+        let mut diagnostics = Diagnostics::new(config);
+        let annotations = diagnostics.annotations(&file, lexed.tokens());
+
+        for token in lexed.tokens().iter().filter(|t| t.lexeme == "let") {
+            let location = SourceCodeLocation::new(Rc::clone(&file), token.line, token.start_char, token.end_char);
+            diagnostics.lint(&UNUSED_VARIABLE, &annotations, token.span, "unused".to_string(), location);
+        }
+
+        return diagnostics.diagnostics().iter()
+            .map(|d| (d.location.as_ref().unwrap().line, d.severity))
+            .collect();
+    }
+
+    #[test]
+    fn test_statement_annotations() {
+        // given
+        let code = "let a = 1;\n@allow(unused_variable)\nlet b = 2;\n@deny(unused_variable) fn f() {\n    let c = 3;\n}\nlet d = 4;";
+
+        // when
+        let found = lint_each_let(code, LintConfig::default());
+
+        // then
+        assert_eq!(found, [(1, Severity::Warning), (5, Severity::Error), (7, Severity::Warning)]);
+    }
+
+    #[test]
+    fn test_file_annotations_override_config() {
+        // given
+        let mut config = LintConfig::default();
+        config.set("unused_variable", Level::Deny).unwrap();
+        let code = "@!warn(unused_variable)\nlet a = 1;\n@allow(unused_variable) let b = 2;";
+
+        // when
+        let found = lint_each_let(code, config);
+
+        // then
+        assert_eq!(found, [(2, Severity::Warning)]);
+    }
+
+    #[test]
+    fn test_deny_warnings() {
+        // given
+        let config = LintConfig { deny_warnings: true, ..LintConfig::default() };
+
+        // when
+        let found = lint_each_let("@allow(unused_varaible)\nlet a = 1;", config);
+
+        // then
+        assert_eq!(found, [(1, Severity::Error), (2, Severity::Error)]);
+        assert!(LintConfig::default().set("unused_varaible", Level::Deny).is_err());
+    }
+}
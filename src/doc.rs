@@ -0,0 +1,367 @@
+use crate::highlight::escape_html;
+use crate::source::SourceFile;
+use crate::symbols::{declarations, DeclarationKind};
+use crate::token::{Token, TokenKind};
+
+/// Documentation of one source file.
+#[derive(Debug, PartialEq)]
+pub struct ModuleDoc {
+    pub name: String,
+    /// Text of the `//!` comments at the top of the file.
+    pub docs: String,
+    pub items: Vec<DocItem>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct DocItem {
+    pub name: String,
+    pub kind: DeclarationKind,
+    pub signature: String,
+    /// Text of the `///` comments right above the declaration.
+    pub docs: String,
+    /// Methods, for classes.
+    pub members: Vec<DocItem>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DocFormat {
+    Markdown,
+    Html,
+}
+
+/// Collects the functions, classes, structs, interfaces and constants of a
+/// file with their doc comments from a lossless token stream. Declarations
+/// inside function bodies are local and left out; functions and constants
+/// inside a class or interface body become its members.
+pub fn document(name: &str, file: &SourceFile, tokens: &[Token]) -> ModuleDoc {
+    let mut items: Vec<(usize, usize, DocItem)> = Vec::new();
+    let mut function_bodies = Vec::new();
+
+    let declarations = declarations(tokens);
+    for declaration in &declarations {
+        if matches!(declaration.kind, DeclarationKind::Parameter | DeclarationKind::Variable) {
+            continue;
+        }
+
+        let name_index = tokens.partition_point(|t| t.span.start < declaration.span.start);
+        let previous = |index: usize| (0..index).rev()
+            .find(|&i| !tokens[i].kind.is_trivia() && tokens[i].kind != TokenKind::Star);
+        let mut keyword = previous(name_index).unwrap_or(name_index);
+        while let Some(modifier) = previous(keyword).filter(|&i| matches!(tokens[i].kind, TokenKind::Static | TokenKind::Async)) {
+            keyword = modifier;
+        }
+        let (end, body) = signature_end(tokens, name_index);
+
+        let item = DocItem {
+            name: declarations.name(declaration).to_string(),
+            kind: declaration.kind,
+            signature: collapse_whitespace(file.slice(tokens[keyword].span.join(tokens[end].span))),
+            docs: doc_comment(tokens, keyword),
+            members: Vec::new(),
+        };
+
+        let (start, end) = body.unwrap_or((keyword, end));
+        if declaration.kind == DeclarationKind::Function && body.is_some() {
+            function_bodies.push((start, end));
+        }
+        items.push((keyword, end, item));
+    }
+
+    let inside = |i: usize, (start, end): (usize, usize)| start < i && i < end;
+    let mut module = ModuleDoc {
+        name: name.to_string(),
+        docs: module_comment(tokens),
+        items: Vec::new(),
+    };
+    // item index and body of each class seen so far
+    let mut classes: Vec<(usize, (usize, usize))> = Vec::new();
+
+    for (keyword, end, item) in items {
+        if function_bodies.iter().any(|body| inside(keyword, *body)) {
+            continue;
+        }
+
+        let class = classes.iter().rev().find(|(_, body)| inside(keyword, *body));
+        match class {
+            Some((index, _)) if matches!(item.kind, DeclarationKind::Function | DeclarationKind::Constant) => {
+                module.items[*index].members.push(item);
+            },
+            _ => {
+                if matches!(item.kind, DeclarationKind::Class | DeclarationKind::Interface) {
+                    classes.push((module.items.len(), (keyword, end)));
+                }
+                module.items.push(item);
+            },
+        }
+    }
+
+    return module;
+}
+
+/// Finds the last token of the signature starting at the declared name:
+/// everything up to a body, `;` or `=>` outside of brackets. Also returns
+/// the token range of the `{...}` body if there is one.
+fn signature_end(tokens: &[Token], name: usize) -> (usize, Option<(usize, usize)>) {
+    let mut depth = 0;
+    let mut last = name;
+
+    for (i, token) in tokens.iter().enumerate().skip(name) {
+        match token.kind {
+            TokenKind::LeftParenthesis | TokenKind::LeftBracket => depth += 1,
+            TokenKind::RightParenthesis | TokenKind::RightBracket => depth -= 1,
+            TokenKind::LeftBrace if depth == 0 => return (last, Some((i, matching_brace(tokens, i)))),
+            TokenKind::Semicolon | TokenKind::FatArrow if depth == 0 => return (last, None),
+            _ => {},
+        }
+
+        if !token.kind.is_trivia() {
+            last = i;
+        }
+    }
+
+    return (last, None);
+}
+
+fn matching_brace(tokens: &[Token], open: usize) -> usize {
+    let mut depth = 0;
+
+    for (i, token) in tokens.iter().enumerate().skip(open) {
+        match token.kind {
+            TokenKind::LeftBrace => depth += 1,
+            TokenKind::RightBrace => depth -= 1,
+            _ => {},
+        }
+
+        if depth == 0 {
+            return i;
+        }
+    }
+
+    return tokens.len();
+}
+
+/// The `///` comments directly above the token at `index`, with no blank
+/// line in between.
+fn doc_comment(tokens: &[Token], index: usize) -> String {
+    let mut lines = Vec::new();
+
+    for token in tokens[..index].iter().rev() {
+        match token.kind {
+            TokenKind::Whitespace if token.lexeme.matches('\n').count() <= 1 => continue,
+            TokenKind::LineComment if token.lexeme.starts_with("///") && !token.lexeme.starts_with("////") => {
+                lines.push(comment_text(&token.lexeme[3..]));
+            },
+            _ => break,
+        }
+    }
+
+    lines.reverse();
+    return lines.join("\n");
+}
+
+/// The `//!` comments at the start of the file.
+fn module_comment(tokens: &[Token]) -> String {
+    return tokens.iter()
+        .take_while(|t| t.kind == TokenKind::Whitespace || (t.kind == TokenKind::LineComment && t.lexeme.starts_with("//!")))
+        .filter(|t| t.kind == TokenKind::LineComment)
+        .map(|t| comment_text(&t.lexeme[3..]))
+        .collect::<Vec<&str>>()
+        .join("\n");
+}
+
+fn comment_text(text: &str) -> &str {
+    return text.strip_prefix(' ').unwrap_or(text).trim_end();
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    return text.split_whitespace().collect::<Vec<&str>>().join(" ");
+}
+
+fn describe(kind: DeclarationKind) -> &'static str {
+    return match kind {
+        DeclarationKind::Function => "fn",
+        DeclarationKind::Class => "class",
+        DeclarationKind::Struct => "struct",
+        DeclarationKind::Interface => "interface",
+        DeclarationKind::Constant => "const",
+        DeclarationKind::Parameter | DeclarationKind::Variable => "let",
+    };
+}
+
+pub fn render(module: &ModuleDoc, format: DocFormat) -> String {
+    let targets: Vec<String> = module.items.iter()
+        .flat_map(|item| std::iter::once(item.name.clone())
+            .chain(item.members.iter().map(|m| format!("{}.{}", item.name, m.name))))
+        .collect();
+
+    return match format {
+        DocFormat::Markdown => render_markdown(module, &targets),
+        DocFormat::Html => render_html(module, &targets),
+    };
+}
+
+fn render_markdown(module: &ModuleDoc, targets: &[String]) -> String {
+    let mut out = format!("# Module `{}`\n\n", module.name);
+
+    if !module.docs.is_empty() {
+        out.push_str(&format!("{}\n\n", link(&module.docs, targets, DocFormat::Markdown)));
+    }
+
+    let mut write_item = |item: &DocItem, anchor: &str, heading: &str| {
+        out.push_str(&format!("<a id=\"{}\"></a>\n{} {} `{}`\n\n```lang3\n{}\n```\n\n",
+                              anchor, heading, describe(item.kind), item.name, item.signature));
+        if !item.docs.is_empty() {
+            out.push_str(&format!("{}\n\n", link(&item.docs, targets, DocFormat::Markdown)));
+        }
+    };
+
+    for item in &module.items {
+        write_item(item, &item.name, "##");
+        for member in &item.members {
+            write_item(member, &format!("{}.{}", item.name, member.name), "###");
+        }
+    }
+
+    return out;
+}
+
+fn render_html(module: &ModuleDoc, targets: &[String]) -> String {
+    let mut out = format!("<h1>Module <code>{}</code></h1>\n", escape_html(&module.name));
+
+    if !module.docs.is_empty() {
+        out.push_str(&format!("<p>{}</p>\n", link(&escape_html(&module.docs), targets, DocFormat::Html)));
+    }
+
+    let mut write_item = |item: &DocItem, anchor: &str, heading: &str| {
+        out.push_str(&format!("<{} id=\"{}\">{} <code>{}</code></{}>\n<pre class=\"lang3\">{}</pre>\n",
+                              heading, escape_html(anchor), describe(item.kind), escape_html(&item.name), heading, escape_html(&item.signature)));
+        if !item.docs.is_empty() {
+            out.push_str(&format!("<p>{}</p>\n", link(&escape_html(&item.docs), targets, DocFormat::Html)));
+        }
+    };
+
+    for item in &module.items {
+        write_item(item, &item.name, "h2");
+        for member in &item.members {
+            write_item(member, &format!("{}.{}", item.name, member.name), "h3");
+        }
+    }
+
+    return out;
+}
+
+/// Turns `[name]` references to items of the module into links.
+fn link(text: &str, targets: &[String], format: DocFormat) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+
+    while let Some(open) = rest.find('[') {
+        out.push_str(&rest[..open]);
+        rest = &rest[open..];
+
+        let target = rest[1..].find(']')
+            .map(|close| &rest[1..close + 1])
+            .filter(|name| targets.iter().any(|t| t == name));
+
+        match target {
+            Some(name) => {
+                match format {
+                    DocFormat::Markdown => out.push_str(&format!("[`{}`](#{})", name, name)),
+                    DocFormat::Html => out.push_str(&format!("<a href=\"#{}\"><code>{}</code></a>", name, name)),
+                }
+                rest = &rest[name.len() + 2..];
+            },
+            None => {
+                out.push('[');
+                rest = &rest[1..];
+            },
+        }
+    }
+
+    out.push_str(rest);
+    return out;
+}
+
+#[cfg(test)]
+mod doc_tests {
+    use crate::incremental::LexedFile;
+    use crate::source::SourceFile;
+    use crate::symbols::DeclarationKind;
+    use super::{document, render, DocFormat};
+
+    const CODE: &str = "//! Geometry helpers.
+
+/// Largest supported size.
+const MAX = 100;
+
+/// A point, see [Point.move].
+class Point {
+    /// Moves the point by
+    /// `dx` and `dy`.
+    fn move(dx, dy) {
+        fn local() {}
+    }
+
+    /// The origin.
+    static async fn origin() {}
+
+    const DIMENSIONS = 2;
+}
+
+// not a doc comment
+fn helper(a,
+          b) => a;
+
+/// Detached.
+
+fn undocumented() {}
+";
+
+    #[test]
+    fn test_document() {
+        // given
+        let file = LexedFile::new(SourceFile::anonymous(CODE.to_string()));
+
+        // when
+        let module = document("geometry", file.file(), file.tokens());
+
+        // then
+        assert_eq!(module.docs, "Geometry helpers.");
+
+        let items: Vec<(&str, DeclarationKind, &str, &str)> = module.items.iter()
+            .map(|i| (i.name.as_str(), i.kind, i.signature.as_str(), i.docs.as_str()))
+            .collect();
+        assert_eq!(items, [
+            ("MAX", DeclarationKind::Constant, "const MAX = 100", "Largest supported size."),
+            ("Point", DeclarationKind::Class, "class Point", "A point, see [Point.move]."),
+            ("helper", DeclarationKind::Function, "fn helper(a, b)", ""),
+            ("undocumented", DeclarationKind::Function, "fn undocumented()", ""),
+        ]);
+
+        let members = &module.items[1].members;
+        assert_eq!(members.len(), 3);
+        assert_eq!(members[0].signature, "fn move(dx, dy)");
+        assert_eq!(members[0].docs, "Moves the point by\n`dx` and `dy`.");
+        assert_eq!(members[1].signature, "static async fn origin()");
+        assert_eq!(members[1].docs, "The origin.");
+        assert_eq!(members[2].signature, "const DIMENSIONS = 2");
+    }
+
+    #[test]
+    fn test_render_links() {
+        // given
+        let file = LexedFile::new(SourceFile::anonymous(CODE.to_string()));
+        let module = document("geometry", file.file(), file.tokens());
+
+        // when
+        let markdown = render(&module, DocFormat::Markdown);
+        let html = render(&module, DocFormat::Html);
+
+        // then
+        assert!(markdown.starts_with("# Module `geometry`\n\nGeometry helpers.\n\n"));
+        assert!(markdown.contains("<a id=\"Point.move\"></a>\n### fn `move`\n\n```lang3\nfn move(dx, dy)\n```\n"));
+        assert!(markdown.contains("A point, see [`Point.move`](#Point.move)."));
+        assert!(html.contains("<h2 id=\"MAX\">const <code>MAX</code></h2>"));
+        assert!(html.contains("see <a href=\"#Point.move\"><code>Point.move</code></a>."));
+    }
+}
@@ -0,0 +1,479 @@
+use std::rc::Rc;
+use crate::lexer::{Lexer, LexerError};
+use crate::source::SourceFile;
+use crate::token::{Token, TokenKind};
+
+const INDENT: &str = "    ";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Delimiter {
+    Parenthesis,
+    Bracket,
+    Block,
+    /// The body of a `do ... while` loop, whose `while` stays after the `}`.
+    DoBlock,
+    /// The statements after a `case ...:` or `default:` label, indented
+    /// one level deeper until the next label or the end of the switch.
+    Case,
+}
+
+/// Re-emits a token stream with canonical indentation, spacing and brace
+/// style. Comments are kept, and at most one blank line is preserved
+/// between statements.
+pub struct Formatter<'a> {
+    file: &'a SourceFile,
+    out: String,
+    delimiters: Vec<Delimiter>,
+    prev: Option<TokenKind>,
+    prev_unary: bool,
+    prev_comment: bool,
+    pending_newline: bool,
+    closed_block: bool,
+    closed_do_block: bool,
+    in_case_label: bool,
+    /// Delimiter depth of the `@name(...)` annotation being written, whose
+    /// closing `)` ends the line.
+    annotation: Option<usize>,
+    closed_annotation: bool,
+    /// Delimiter depth of every `?` still waiting for its `:`, which is
+    /// spaced like a binary operator unlike the one of a label or a key.
+    ternaries: Vec<usize>,
+}
+
+pub fn format_source(file: &Rc<SourceFile>) -> Result<String, LexerError> {
+    let mut lexer = Lexer::from_source_file(file).with_trivia();
+    let mut tokens = Vec::new();
+
+    while let Some(token) = lexer.next_token() {
+        tokens.push(token?);
+    }
+
+    let mut formatter = Formatter::new(file);
+    formatter.format(&tokens);
+
+    return Ok(formatter.finish());
+}
+
+impl<'a> Formatter<'a> {
+    pub fn new(file: &'a SourceFile) -> Self {
+        return Formatter {
+            file,
+            out: String::new(),
+            delimiters: Vec::new(),
+            prev: None,
+            prev_unary: false,
+            prev_comment: false,
+            pending_newline: false,
+            closed_block: false,
+            closed_do_block: false,
+            in_case_label: false,
+            annotation: None,
+            closed_annotation: false,
+            ternaries: Vec::new(),
+        };
+    }
+
+    pub fn format(&mut self, tokens: &[Token]) {
+        let mut newlines = 0;
+
+        for token in tokens {
+            match token.kind {
+                TokenKind::Whitespace => {
+                    newlines += token.lexeme.matches('\n').count();
+                },
+                TokenKind::LineComment | TokenKind::BlockComment => {
+                    self.write_comment(token, newlines);
+                    newlines = 0;
+                },
+                _ => {
+                    self.write_token(token, newlines);
+                    newlines = 0;
+                },
+            }
+        }
+    }
+
+    pub fn finish(mut self) -> String {
+        self.trim_trailing_spaces();
+        if !self.out.is_empty() {
+            self.out.push('\n');
+        }
+        return self.out;
+    }
+
+    fn write_comment(&mut self, token: &Token, newlines: usize) {
+        let own_line = newlines > 0 || self.prev.is_none();
+
+        if own_line {
+            self.newline(newlines);
+        } else if !self.at_line_start() && !matches!(self.prev, Some(TokenKind::LeftParenthesis | TokenKind::LeftBracket)) {
+            self.out.push(' ');
+        }
+
+        self.out.push_str(self.file.slice(token.span));
+
+        // a line comment always ends its line, a block comment on its own
+        // line keeps the code after it on the next one, and a trailing one
+        // leaves the line break owed by the code before it in place
+        self.pending_newline |= token.kind == TokenKind::LineComment || own_line;
+        self.prev_comment = true;
+    }
+
+    fn write_token(&mut self, token: &Token, newlines: usize) {
+        let kind = token.kind;
+        let prev = self.prev;
+
+        if matches!(kind, TokenKind::Case | TokenKind::Default | TokenKind::RightBrace)
+            && self.delimiters.last() == Some(&Delimiter::Case) {
+            self.delimiters.pop();
+        }
+
+        if kind == TokenKind::RightBrace && matches!(self.delimiters.last(), Some(Delimiter::Block | Delimiter::DoBlock)) {
+            self.closed_do_block = self.delimiters.pop() == Some(Delimiter::DoBlock);
+            // an empty block stays on one line as `{}`
+            self.pending_newline = self.prev != Some(TokenKind::LeftBrace) || self.prev_comment;
+        } else if matches!(kind, TokenKind::RightParenthesis | TokenKind::RightBracket) {
+            self.delimiters.pop();
+        }
+
+        let depth = self.delimiters.len();
+        self.ternaries.retain(|d| *d <= depth);
+
+        if self.closed_block && self.continues_block(kind) {
+            self.pending_newline = false;
+        }
+
+        if self.pending_newline {
+            let blank = if self.prev_comment || self.closed_block || self.ends_statement() {
+                newlines
+            } else {
+                0
+            };
+            self.newline(blank);
+        } else if self.needs_space(kind) {
+            self.out.push(' ');
+        }
+
+        self.out.push_str(self.file.slice(token.span));
+
+        self.prev_unary = self.is_unary(kind);
+        self.prev = Some(kind);
+        self.prev_comment = false;
+        self.closed_block = false;
+        self.closed_annotation = false;
+
+        match kind {
+            TokenKind::LeftBrace => {
+                let block = if prev == Some(TokenKind::Do) { Delimiter::DoBlock } else { Delimiter::Block };
+                self.delimiters.push(block);
+                self.pending_newline = true;
+            },
+            TokenKind::LeftParenthesis => self.delimiters.push(Delimiter::Parenthesis),
+            TokenKind::LeftBracket => self.delimiters.push(Delimiter::Bracket),
+            TokenKind::RightBrace => self.closed_block = true,
+            TokenKind::At => self.annotation = Some(self.delimiters.len()),
+            TokenKind::Questionmark => self.ternaries.push(self.delimiters.len()),
+            TokenKind::Colon if self.ternaries.last() == Some(&self.delimiters.len()) => {
+                self.ternaries.pop();
+            },
+            TokenKind::Semicolon => self.ternaries.clear(),
+            TokenKind::RightParenthesis if self.annotation == Some(self.delimiters.len()) => {
+                self.annotation = None;
+                self.closed_annotation = true;
+                self.pending_newline = true;
+            },
+            TokenKind::Case | TokenKind::Default => self.in_case_label = true,
+            TokenKind::Colon if self.in_case_label && !self.in_parentheses() => {
+                self.in_case_label = false;
+                self.delimiters.push(Delimiter::Case);
+                self.pending_newline = true;
+            },
+            _ => {},
+        }
+
+        if kind == TokenKind::RightBrace {
+            self.pending_newline = true;
+        }
+
+        if kind == TokenKind::Semicolon && !self.in_parentheses() {
+            self.pending_newline = true;
+        }
+    }
+
+    fn in_parentheses(&self) -> bool {
+        return matches!(self.delimiters.last(), Some(Delimiter::Parenthesis | Delimiter::Bracket));
+    }
+
+    /// Tokens that stay on the same line as the `}` before them.
+    fn continues_block(&self, kind: TokenKind) -> bool {
+        return (kind == TokenKind::While && self.closed_do_block) || matches!(kind,
+            TokenKind::Else
+            | TokenKind::Catch
+            | TokenKind::Finally
+            | TokenKind::RightParenthesis
+            | TokenKind::RightBracket
+            | TokenKind::Comma
+            | TokenKind::Semicolon
+            | TokenKind::Dot
+            | TokenKind::QuestionmarkDot);
+    }
+
+    fn ends_statement(&self) -> bool {
+        return self.prev == Some(TokenKind::Semicolon) || self.closed_annotation;
+    }
+
+    fn needs_space(&self, kind: TokenKind) -> bool {
+        let prev = match self.prev {
+            Some(prev) => prev,
+            None => return false,
+        };
+
+        if self.prev_comment {
+            return true;
+        }
+
+        if self.prev_unary || (prev == TokenKind::LeftBrace && kind == TokenKind::RightBrace) {
+            return false;
+        }
+
+        if matches!(prev,
+            TokenKind::LeftParenthesis
+            | TokenKind::LeftBracket
+            | TokenKind::Dot
+            | TokenKind::QuestionmarkDot
+            | TokenKind::DotDot
+            | TokenKind::DotDotEqual
+            | TokenKind::DotDotDot) {
+            return false;
+        }
+
+        return match kind {
+            TokenKind::Comma
+            | TokenKind::Semicolon
+            | TokenKind::RightParenthesis
+            | TokenKind::RightBracket
+            | TokenKind::Dot
+            | TokenKind::QuestionmarkDot
+            | TokenKind::DotDot
+            | TokenKind::DotDotEqual => false,
+            TokenKind::LeftParenthesis | TokenKind::LeftBracket => !self.is_operand_end(prev) && prev != TokenKind::Fn,
+            TokenKind::PlusPlus | TokenKind::MinusMinus => !self.is_operand_end(prev),
+            TokenKind::Star => prev != TokenKind::Fn,
+            TokenKind::Colon => self.ternaries.last() == Some(&self.delimiters.len()),
+            _ => true,
+        };
+    }
+
+    /// Whether `kind`, just written, is a prefix operator that binds to the
+    /// token after it.
+    fn is_unary(&self, kind: TokenKind) -> bool {
+        let after_operand = self.prev.is_some_and(|prev| self.is_operand_end(prev));
+
+        return match kind {
+            TokenKind::Bang | TokenKind::Tilde | TokenKind::DotDotDot | TokenKind::At => true,
+            TokenKind::Minus | TokenKind::Plus | TokenKind::PlusPlus | TokenKind::MinusMinus => !after_operand,
+            _ => false,
+        };
+    }
+
+    fn is_operand_end(&self, kind: TokenKind) -> bool {
+        return matches!(kind,
+            TokenKind::Identifier
+            | TokenKind::String
+            | TokenKind::Char
+            | TokenKind::Integer
+            | TokenKind::Float
+            | TokenKind::True
+            | TokenKind::False
+            | TokenKind::Null
+            | TokenKind::This
+            | TokenKind::Super
+            | TokenKind::Print
+            | TokenKind::RightParenthesis
+            | TokenKind::RightBracket
+            | TokenKind::RightBrace
+            | TokenKind::PlusPlus
+            | TokenKind::MinusMinus);
+    }
+
+    fn newline(&mut self, newlines: usize) {
+        self.trim_trailing_spaces();
+
+        if !self.out.is_empty() {
+            self.out.push('\n');
+            if newlines > 1 && self.prev != Some(TokenKind::LeftBrace) {
+                self.out.push('\n');
+            }
+        }
+
+        let depth = self.delimiters.iter()
+            .filter(|d| matches!(d, Delimiter::Block | Delimiter::DoBlock | Delimiter::Case))
+            .count();
+        for _ in 0..depth {
+            self.out.push_str(INDENT);
+        }
+
+        self.pending_newline = false;
+    }
+
+    fn at_line_start(&self) -> bool {
+        return self.out.is_empty() || self.out.ends_with('\n');
+    }
+
+    fn trim_trailing_spaces(&mut self) {
+        let len = self.out.trim_end_matches(' ').len();
+        self.out.truncate(len);
+    }
+}
+
+#[cfg(test)]
+mod formatter_tests {
+    use std::path::PathBuf;
+    use crate::source::SourceMap;
+    use super::format_source;
+
+    fn format(code: &str) -> String {
+        let mut map = SourceMap::new();
+        let id = map.add_file(PathBuf::from("test.l3"), code.to_string());
+        return format_source(map.get(id)).unwrap();
+    }
+
+    #[test]
+    fn test_format_spacing() {
+        // given
+        let code = "let x=-1+foo(a,b)[0]*2;x++;print(!x, a ?. b??c);";
+
+        // when
+        let formatted = format(code);
+
+        // then
+        assert_eq!(formatted, "let x = -1 + foo(a, b)[0] * 2;\nx++;\nprint(!x, a?.b ?? c);\n");
+    }
+
+    #[test]
+    fn test_format_blocks() {
+        // given
+        let code = "fn main(){if(x){return 1;}else{}\n\n\n// done\nwhile(true){}}";
+
+        // when
+        let formatted = format(code);
+
+        // then
+        assert_eq!(formatted, "\
+fn main() {
+    if (x) {
+        return 1;
+    } else {}
+
+    // done
+    while (true) {}
+}
+");
+    }
+
+    #[test]
+    fn test_format_do_while() {
+        // given
+        let code = "do{x++;}while(x<10);\nif(x){}\nwhile(x){x--;}";
+
+        // when
+        let formatted = format(code);
+
+        // then
+        assert_eq!(formatted, "do {\n    x++;\n} while (x < 10);\nif (x) {}\nwhile (x) {\n    x--;\n}\n");
+    }
+
+    #[test]
+    fn test_format_switch() {
+        // given
+        let code = "switch(x){case 1: case 2:print(a);break;default:{}}";
+
+        // when
+        let formatted = format(code);
+
+        // then
+        assert_eq!(formatted, "\
+switch (x) {
+    case 1:
+    case 2:
+        print(a);
+        break;
+    default:
+        {}
+}
+");
+    }
+
+    #[test]
+    fn test_format_ternary() {
+        // given
+        let code = "let a = x?1:f(y ? 2 : 3);\nlet o = {k: c ? [1] : {n: 2}};\nswitch (a) { case 1: b = c ? d : e; }";
+
+        // when
+        let formatted = format(code);
+
+        // then
+        assert_eq!(formatted, "\
+let a = x ? 1 : f(y ? 2 : 3);
+let o = {
+    k: c ? [1] : {
+        n: 2
+    }
+};
+switch (a) {
+    case 1:
+        b = c ? d : e;
+}
+");
+    }
+
+    #[test]
+    fn test_format_annotations() {
+        // given
+        let code = "@ ! allow(shadowed_variable)\n\nfn f(x) { @allow( unused_variable ) let y = 1; }\n@deny(a, b) let z = 2;";
+
+        // when
+        let formatted = format(code);
+
+        // then
+        assert_eq!(formatted, "\
+@!allow(shadowed_variable)
+
+fn f(x) {
+    @allow(unused_variable)
+    let y = 1;
+}
+@deny(a, b)
+let z = 2;
+");
+    }
+
+    #[test]
+    fn test_format_keeps_literals_and_comments() {
+        // given
+        let code = "let s = \"a\\tb\"; /* keep */ let r = 0..=10; // trailing\n";
+
+        // when
+        let formatted = format(code);
+
+        // then
+        assert_eq!(formatted, "let s = \"a\\tb\"; /* keep */\nlet r = 0..=10; // trailing\n");
+    }
+
+    #[test]
+    fn test_format_is_idempotent() {
+        let inputs = [
+            "fn  main ( ) { let x = [1,2 ,3]; foreach(i in x){ print(i) ;} }",
+            "// header\n\nlet a = b ?? c;\n\n\nlet d = e -> f;",
+            "fn* range(n) { let i = 0; while (i < n) { yield i; i += 1; } }",
+            "try { throw x; } catch (e) { /* ignore */ } finally { done(); }",
+            "@!allow(empty_block)\nfn f() { @allow(unused_variable) let x = g(1); if (x) {} }",
+        ];
+
+        for input in inputs {
+            let once = format(input);
+            let twice = format(&once);
+            assert_eq!(once, twice, "{}", input);
+        }
+    }
+}
@@ -0,0 +1,179 @@
+use colored::{ColoredString, Colorize};
+use crate::source::SourceFile;
+use crate::symbols::{declarations, DeclarationKind};
+use crate::token::{Token, TokenKind};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HighlightKind {
+    Keyword,
+    Function,
+    Parameter,
+    Variable,
+    Type,
+    Constant,
+    String,
+    Number,
+    Comment,
+    Operator,
+    Plain,
+}
+
+impl HighlightKind {
+    /// Name used as the CSS class in HTML output.
+    pub fn name(self) -> &'static str {
+        return match self {
+            HighlightKind::Keyword => "keyword",
+            HighlightKind::Function => "function",
+            HighlightKind::Parameter => "parameter",
+            HighlightKind::Variable => "variable",
+            HighlightKind::Type => "type",
+            HighlightKind::Constant => "constant",
+            HighlightKind::String => "string",
+            HighlightKind::Number => "number",
+            HighlightKind::Comment => "comment",
+            HighlightKind::Operator => "operator",
+            HighlightKind::Plain => "plain",
+        };
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HighlightFormat {
+    Html,
+    Ansi,
+}
+
+/// Classifies every token, one kind per token. Identifiers are looked up
+/// in the declarations of the same token stream, so a name is highlighted
+/// as whatever it was declared as; undeclared names followed by `(` are
+/// taken to be functions.
+pub fn highlight(tokens: &[Token]) -> Vec<HighlightKind> {
+    let declarations = declarations(tokens);
+    let next_kind = |i: usize| tokens[i + 1..].iter()
+        .map(|t| t.kind)
+        .find(|k| !k.is_trivia());
+
+    return tokens.iter().enumerate()
+        .map(|(i, token)| match token.kind {
+            TokenKind::Identifier => match declarations.find(&token.lexeme, token.span.start) {
+                Some(declaration) => match declaration.kind {
+                    DeclarationKind::Function => HighlightKind::Function,
+                    DeclarationKind::Parameter => HighlightKind::Parameter,
+                    DeclarationKind::Variable => HighlightKind::Variable,
+                    DeclarationKind::Constant => HighlightKind::Constant,
+                    DeclarationKind::Class | DeclarationKind::Struct | DeclarationKind::Interface => HighlightKind::Type,
+                },
+                None if next_kind(i) == Some(TokenKind::LeftParenthesis) => HighlightKind::Function,
+                None => HighlightKind::Variable,
+            },
+            TokenKind::String | TokenKind::Char => HighlightKind::String,
+            TokenKind::Integer | TokenKind::Float => HighlightKind::Number,
+            TokenKind::LineComment | TokenKind::BlockComment => HighlightKind::Comment,
+            TokenKind::Whitespace | TokenKind::Invalid => HighlightKind::Plain,
+            _ if token.lexeme.chars().all(char::is_alphabetic) => HighlightKind::Keyword,
+            _ => HighlightKind::Operator,
+        })
+        .collect();
+}
+
+/// Renders the source text of a lossless token stream, such as the one
+/// kept by `LexedFile`, with the highlighting applied.
+pub fn render(file: &SourceFile, tokens: &[Token], format: HighlightFormat) -> String {
+    let kinds = highlight(tokens);
+    let mut out = String::new();
+
+    if format == HighlightFormat::Html {
+        out.push_str("<pre class=\"lang3\">");
+    }
+
+    for (token, kind) in tokens.iter().zip(kinds) {
+        let text = file.slice(token.span);
+
+        match format {
+            HighlightFormat::Html if kind == HighlightKind::Plain => out.push_str(&escape_html(text)),
+            HighlightFormat::Html => {
+                out.push_str(&format!("<span class=\"{}\">{}</span>", kind.name(), escape_html(text)));
+            },
+            HighlightFormat::Ansi => out.push_str(&colorize(text, kind).to_string()),
+        }
+    }
+
+    if format == HighlightFormat::Html {
+        out.push_str("</pre>\n");
+    }
+
+    return out;
+}
+
+pub(crate) fn colorize(text: &str, kind: HighlightKind) -> ColoredString {
+    return match kind {
+        HighlightKind::Keyword => text.magenta().bold(),
+        HighlightKind::Function => text.blue(),
+        HighlightKind::Parameter => text.italic(),
+        HighlightKind::Type => text.yellow(),
+        HighlightKind::Constant | HighlightKind::Number => text.cyan(),
+        HighlightKind::String => text.green(),
+        HighlightKind::Comment => text.bright_black(),
+        HighlightKind::Variable | HighlightKind::Operator | HighlightKind::Plain => text.normal(),
+    };
+}
+
+pub(crate) fn escape_html(text: &str) -> String {
+    return text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;");
+}
+
+#[cfg(test)]
+mod highlight_tests {
+    use crate::incremental::LexedFile;
+    use crate::source::SourceFile;
+    use super::{highlight, render, HighlightFormat, HighlightKind};
+
+    #[test]
+    fn test_highlight() {
+        // given
+        let file = LexedFile::new(SourceFile::anonymous("class A {}\nconst C = 1;\nfn f(p) { print(p, C, \"s\"); } // c".to_string()));
+
+        // when
+        let kinds = highlight(file.tokens());
+
+        // then
+        let found: Vec<(&str, HighlightKind)> = file.tokens().iter()
+            .zip(kinds)
+            .filter(|(_, k)| *k != HighlightKind::Plain && *k != HighlightKind::Operator)
+            .map(|(t, k)| (file.file().slice(t.span), k))
+            .collect();
+        assert_eq!(found, [
+            ("class", HighlightKind::Keyword),
+            ("A", HighlightKind::Type),
+            ("const", HighlightKind::Keyword),
+            ("C", HighlightKind::Constant),
+            ("1", HighlightKind::Number),
+            ("fn", HighlightKind::Keyword),
+            ("f", HighlightKind::Function),
+            ("p", HighlightKind::Parameter),
+            ("print", HighlightKind::Keyword),
+            ("p", HighlightKind::Parameter),
+            ("C", HighlightKind::Constant),
+            ("\"s\"", HighlightKind::String),
+            ("// c", HighlightKind::Comment),
+        ]);
+    }
+
+    #[test]
+    fn test_render_html() {
+        // given
+        let file = LexedFile::new(SourceFile::anonymous("let a = b < 1;".to_string()));
+
+        // when
+        let html = render(file.file(), file.tokens(), HighlightFormat::Html);
+
+        // then
+        assert_eq!(html, "<pre class=\"lang3\"><span class=\"keyword\">let</span> \
+            <span class=\"variable\">a</span> <span class=\"operator\">=</span> \
+            <span class=\"variable\">b</span> <span class=\"operator\">&lt;</span> \
+            <span class=\"number\">1</span><span class=\"operator\">;</span></pre>\n");
+    }
+}
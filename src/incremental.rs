@@ -0,0 +1,219 @@
+use std::ops::Range;
+use std::rc::Rc;
+use crate::lexer::Lexer;
+use crate::source::{FileId, SourceFile, Span};
+use crate::token::{Token, TokenKind};
+
+/// A source file together with its lossless token stream (trivia
+/// included), kept up to date as the text is edited.
+///
+/// An edit only re-lexes from the token before it until the new tokens
+/// line up with the old ones again; everything after that point is reused
+/// and only has its position shifted. Edits change the file in place, so
+/// clones of the `Rc` from `file()` shouldn't outlive a call: while one is
+/// alive, the next edit has to copy the whole file first.
+pub struct LexedFile {
+    file: Rc<SourceFile>,
+    tokens: Vec<Token>,
+}
+
+impl LexedFile {
+    pub fn new(file: SourceFile) -> Self {
+        let file = Rc::new(file);
+        let mut lexer = Lexer::from_source_file(&file).with_trivia();
+        let mut tokens = Vec::new();
+
+        while let Some(token) = next_token(&mut lexer, file.id) {
+            tokens.push(token);
+        }
+
+        return LexedFile { file, tokens };
+    }
+
+    pub fn file(&self) -> &Rc<SourceFile> {
+        return &self.file;
+    }
+
+    /// Every token of the file, in order and without gaps. Input that
+    /// failed to lex is covered by `TokenKind::Invalid` tokens whose
+    /// lexeme is the error message.
+    pub fn tokens(&self) -> &[Token] {
+        return &self.tokens;
+    }
+
+    pub fn errors(&self) -> impl Iterator<Item = &Token> {
+        return self.tokens.iter().filter(|t| t.kind == TokenKind::Invalid);
+    }
+
+    /// Replaces the text in `span` with `text` and updates the tokens.
+    /// Returns the index range of the tokens that were re-lexed.
+    pub fn apply_edit(&mut self, span: Span, text: &str) -> Range<usize> {
+        Rc::make_mut(&mut self.file).apply_edit(span, text);
+
+        let old_len = self.tokens.len();
+        let edit_end = span.start + text.len();
+        let shift = |offset: usize| offset + text.len() - span.len();
+
+        // the token right before the edit may merge with the edited text,
+        // e.g. appending to an identifier, so lexing restarts there
+        let restart = self.tokens.partition_point(|t| t.span.end <= span.start).saturating_sub(1);
+        let (offset, line, char) = match self.tokens.get(restart) {
+            Some(token) => (token.span.start, token.line, token.start_char),
+            None => (0, 1, 1),
+        };
+
+        let mut lexer = Lexer::starting_at(&self.file, offset, line, char).with_trivia();
+        let mut relexed = Vec::new();
+        let mut old = self.tokens.partition_point(|t| t.span.start < span.end);
+        let mut synced = None;
+
+        while let Some(token) = next_token(&mut lexer, self.file.id) {
+            if token.span.start >= edit_end {
+                while old < old_len && shift(self.tokens[old].span.start) < token.span.start {
+                    old += 1;
+                }
+
+                // the lexer carries no state between tokens, so from here
+                // on it would produce exactly the old tokens again
+                if old < old_len && shift(self.tokens[old].span.start) == token.span.start {
+                    synced = Some(token);
+                    break;
+                }
+            }
+
+            relexed.push(token);
+        }
+
+        let inserted = restart..restart + relexed.len();
+
+        match synced {
+            Some(token) => {
+                self.shift_tokens(old, &token, &shift);
+                self.tokens.splice(restart..old, relexed);
+            },
+            None => {
+                self.tokens.splice(restart..old_len, relexed);
+            },
+        }
+
+        return inserted;
+    }
+
+    /// Moves the tokens from `first` on to where the edit put them, using
+    /// `synced`, the re-lexed copy of token `first`, as the reference.
+    fn shift_tokens(&mut self, first: usize, synced: &Token, shift: &dyn Fn(usize) -> usize) {
+        let line = self.tokens[first].line;
+        let char = self.tokens[first].start_char;
+
+        for token in &mut self.tokens[first..] {
+            // only columns on the sync token's line moved, anything after
+            // a later newline keeps its column
+            if token.line == line {
+                token.start_char = token.start_char - char + synced.start_char;
+                if !self.file.text[shift(token.span.start)..shift(token.span.end)].contains('\n') {
+                    token.end_char = token.end_char - char + synced.start_char;
+                }
+            }
+
+            token.line = token.line - line + synced.line;
+            token.span = Span::new(shift(token.span.start), shift(token.span.end));
+        }
+    }
+}
+
+fn next_token(lexer: &mut Lexer, file: FileId) -> Option<Token> {
+    let start = lexer.position();
+    let line = lexer.line();
+    let char = lexer.char();
+
+    return match lexer.next_token()? {
+        Ok(token) => Some(token),
+        // an error that consumed nothing would be reported forever
+        Err(_) if lexer.position() == start => None,
+        Err(err) => Some(Token {
+            kind: TokenKind::Invalid,
+            file,
+            lexeme: err.message().to_string(),
+            line,
+            start_char: char,
+            end_char: lexer.char(),
+            span: Span::new(start, lexer.position()),
+        }),
+    };
+}
+
+#[cfg(test)]
+mod incremental_tests {
+    use std::rc::Rc;
+    use crate::source::{SourceFile, Span};
+    use super::LexedFile;
+
+    type Summary = Vec<(String, usize, usize, usize, usize, usize)>;
+
+    fn summary(file: &LexedFile) -> Summary {
+        return file.tokens().iter()
+            .map(|t| (format!("{:?}:{}", t.kind, t.lexeme), t.line, t.start_char, t.end_char, t.span.start, t.span.end))
+            .collect();
+    }
+
+    fn fresh(file: &LexedFile) -> Summary {
+        return summary(&LexedFile::new(SourceFile::anonymous(file.file().text.clone())));
+    }
+
+    #[test]
+    fn test_edit_matches_full_lex() {
+        // given
+        let code = "fn main() {\n    let x = 10; // count\n    print(\"a\\nb\", x);\n}\n/* end */\n";
+        let edits: [(usize, usize, &str); 8] = [
+            (20, 22, "1234"),
+            (3, 7, "start"),
+            (0, 0, "\"open "),
+            (0, 6, ""),
+            (30, 30, "\n\n  "),
+            (12, 13, "/*"),
+            (12, 14, ""),
+            (0, 0, "/* "),
+        ];
+
+        // when
+        let mut file = LexedFile::new(SourceFile::anonymous(code.to_string()));
+
+        for (start, end, text) in edits {
+            file.apply_edit(Span::new(start, end), text);
+
+            // then
+            assert_eq!(summary(&file), fresh(&file), "after replacing {}..{} with {:?}", start, end, text);
+        }
+    }
+
+    #[test]
+    fn test_edit_relexes_locally() {
+        // given
+        let line = "let value = compute(1, 2);\n";
+        let mut file = LexedFile::new(SourceFile::anonymous(line.repeat(1000)));
+        let offset = line.len() * 500 + 4;
+
+        // when
+        let relexed = file.apply_edit(Span::new(offset, offset + 5), "renamed");
+
+        // then
+        assert!(relexed.len() <= 3, "{:?}", relexed);
+        assert_eq!(file.tokens()[relexed.start + 1].lexeme, "renamed");
+        assert_eq!(summary(&file), fresh(&file));
+        assert_eq!(Rc::strong_count(file.file()), 1);
+    }
+
+    #[test]
+    fn test_errors_become_invalid_tokens() {
+        // given
+        let mut file = LexedFile::new(SourceFile::anonymous("let s = 1;".to_string()));
+
+        // when
+        file.apply_edit(Span::new(8, 8), "\"");
+
+        // then
+        let errors: Vec<&str> = file.errors().map(|t| t.lexeme.as_str()).collect();
+        assert_eq!(errors, ["Unterminated string literal"]);
+        assert_eq!(summary(&file), fresh(&file));
+    }
+}
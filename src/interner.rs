@@ -0,0 +1,71 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Interned string. Two symbols of the same interner are equal exactly
+/// when their strings are, so comparing and hashing names is a single
+/// integer operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol(u32);
+
+/// Owns the strings behind a set of symbols. There is no global table:
+/// whoever interns the names keeps the interner and the strings are freed
+/// with it, so a long-running process like the language server doesn't
+/// pile up every name it has ever seen.
+#[derive(Debug, Default)]
+pub struct Interner {
+    ids: HashMap<Rc<str>, Symbol>,
+    strings: Vec<Rc<str>>,
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        return Interner::default();
+    }
+
+    pub fn intern(&mut self, string: &str) -> Symbol {
+        if let Some(symbol) = self.ids.get(string) {
+            return *symbol;
+        }
+
+        let string: Rc<str> = Rc::from(string);
+        let symbol = Symbol(self.strings.len() as u32);
+        self.strings.push(Rc::clone(&string));
+        self.ids.insert(string, symbol);
+
+        return symbol;
+    }
+
+    /// Symbol of a string that was already interned, without adding it.
+    pub fn get(&self, string: &str) -> Option<Symbol> {
+        return self.ids.get(string).copied();
+    }
+
+    /// Panics if the symbol comes from another interner.
+    pub fn resolve(&self, symbol: Symbol) -> &str {
+        return &self.strings[symbol.0 as usize];
+    }
+}
+
+#[cfg(test)]
+mod interner_tests {
+    use super::Interner;
+
+    #[test]
+    fn test_intern() {
+        // given
+        let mut interner = Interner::new();
+        let name = String::from("counter");
+
+        // when
+        let first = interner.intern(&name);
+        let second = interner.intern("counter");
+        let other = interner.intern("count");
+
+        // then
+        assert_eq!(first, second);
+        assert_ne!(first, other);
+        assert_eq!(interner.resolve(first), "counter");
+        assert_eq!(interner.get("count"), Some(other));
+        assert_eq!(interner.get("total"), None);
+    }
+}
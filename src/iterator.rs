@@ -18,6 +18,12 @@ impl<'a> StringIterator<'a> {
         StringIterator { text: s, cur: 0, cur_char: 1, cur_line: 1 }
     }
 
+    /// Starts iterating at byte `offset`, which the caller already knows is
+    /// at `line` and `char`.
+    pub fn starting_at(s: &'a String, offset: usize, line: usize, char: usize) -> Self {
+        StringIterator { text: s, cur: offset, cur_char: char, cur_line: line }
+    }
+
     pub fn text(&self) -> &String {
         return self.text;
     }
@@ -29,29 +35,23 @@ impl<'a> StringIterator<'a> {
     pub fn line(&self) -> usize {
         return self.cur_line;
     }
+
+    /// Byte offset of the next character in the text.
+    pub fn position(&self) -> usize {
+        return self.cur;
+    }
 }
 
 impl PeekableIterator for StringIterator<'_> {
     type Item = char;
 
     fn peek(&self) -> Option<Self::Item> {
-        if self.cur >= self.text.len() {
-            return None
-        }
-
-        let b = self.text.as_bytes()[self.cur];
-
-        return Some(b as char);
+        return self.text[self.cur..].chars().next();
     }
 
+    /// The character `offset` characters after the next one.
     fn offset(&self, offset: usize) -> Option<Self::Item> {
-        if self.cur + offset >= self.text.len() {
-            return None
-        }
-
-        let b = self.text.as_bytes()[self.cur + offset];
-
-        return Some(b as char);
+        return self.text[self.cur..].chars().nth(offset);
     }
 }
 
@@ -59,15 +59,9 @@ impl Iterator for StringIterator<'_> {
     type Item = char;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.cur >= self.text.len() {
-            return None
-        }
-
-        let b = self.text.as_bytes()[self.cur];
-
-        self.cur += 1;
+        let as_char = self.text[self.cur..].chars().next()?;
 
-        let as_char =  b as char;
+        self.cur += as_char.len_utf8();
 
         if as_char == '\n' {
             self.cur_line += 1;
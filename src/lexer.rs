@@ -1,13 +1,17 @@
 use std::error::Error;
 use std::fmt::{Display, Formatter};
+use std::rc::Rc;
+use std::str::FromStr;
 use crate::iterator::{PeekableIterator, StringIterator};
-use crate::source::SourceCodeLocation;
+use crate::source::{SourceCodeLocation, SourceFile, Span};
 use crate::token::{Token, TokenKind};
-use crate::util::{print_location, resolve_escape_sequence};
+use crate::util::{print_file_location, print_location, resolve_escape_sequence};
 
 pub struct Lexer<'a> {
     iter: StringIterator<'a>,
+    file: Rc<SourceFile>,
     state: LexerState,
+    trivia: bool,
 }
 
 
@@ -28,13 +32,15 @@ impl Default for LexerState {
 pub struct LexerError {
     msg: String,
     location: Option<SourceCodeLocation>,
+    unterminated: bool,
 }
 
 impl LexerError {
-    pub fn from_indices(msg: String, text: &String, line: usize, start_char: usize, end_char: usize) -> Self {
+    pub fn from_indices(msg: String, file: Rc<SourceFile>, line: usize, start_char: usize, end_char: usize) -> Self {
         return LexerError {
             msg,
-            location: Option::from(SourceCodeLocation::new(text.clone(), line, start_char, end_char)),
+            location: Option::from(SourceCodeLocation::new(file, line, start_char, end_char)),
+            unterminated: false,
         };
     }
 
@@ -42,6 +48,17 @@ impl LexerError {
         return LexerError {
             msg,
             location: Some(location),
+            unterminated: false,
+        };
+    }
+
+    /// Error for a literal or comment that was still open when the input ran
+    /// out. More input could make it valid, which the REPL relies on.
+    pub fn unterminated(msg: String, location: SourceCodeLocation) -> Self {
+        return LexerError {
+            msg,
+            location: Some(location),
+            unterminated: true,
         };
     }
 
@@ -49,18 +66,30 @@ impl LexerError {
         return LexerError {
             msg: "Invalid escape sequence".to_string(),
             location: Some(location),
+            unterminated: false,
         };
     }
+
+    pub fn is_unterminated(&self) -> bool {
+        return self.unterminated;
+    }
+
+    pub fn message(&self) -> &str {
+        return &self.msg;
+    }
+
+    pub fn location(&self) -> Option<&SourceCodeLocation> {
+        return self.location.as_ref();
+    }
 }
 
 impl Error for LexerError {}
 
 impl Display for LexerError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        if self.location.is_some() {
-            let location = self.location.as_ref().unwrap();
-
-            print_location(&location.text, location.line, location.start_char, location.end_char);
+        if let Some(location) = &self.location {
+            print_file_location(location);
+            print_location(&location.file, location.line, location.start_char, location.end_char);
         }
 
         return write!(f, "Lexer error: {}", self.msg);
@@ -71,36 +100,101 @@ impl<'a> Lexer<'a> {
     pub fn new(text: &'a String) -> Self {
         return Lexer {
             iter: StringIterator::new(text),
+            file: Rc::new(SourceFile::anonymous(text.clone())),
+            state: LexerState::default(),
+            trivia: false,
+        };
+    }
+
+    pub fn from_source_file(file: &'a Rc<SourceFile>) -> Self {
+        return Lexer {
+            iter: StringIterator::new(&file.text),
+            file: Rc::clone(file),
             state: LexerState::default(),
+            trivia: false,
         };
     }
 
+    /// Resumes lexing `file` at byte `offset`, which must be the start of a
+    /// token located at `line` and `char`.
+    pub fn starting_at(file: &'a Rc<SourceFile>, offset: usize, line: usize, char: usize) -> Self {
+        return Lexer {
+            iter: StringIterator::starting_at(&file.text, offset, line, char),
+            file: Rc::clone(file),
+            state: LexerState::default(),
+            trivia: false,
+        };
+    }
+
+    /// Byte offset of the next character to be lexed.
+    pub fn position(&self) -> usize {
+        return self.iter.position();
+    }
+
+    pub fn line(&self) -> usize {
+        return self.iter.line();
+    }
+
+    pub fn char(&self) -> usize {
+        return self.iter.char();
+    }
+
+    /// Makes the lexer emit whitespace and comments as tokens instead of
+    /// skipping them, so the token stream covers every byte of the input.
+    pub fn with_trivia(mut self) -> Self {
+        self.trivia = true;
+        return self;
+    }
+
     pub fn next_token(&mut self) -> Option<Result<Token, LexerError>> {
         if self.state == LexerState::Done {
             return None;
         }
 
-        let c = match self.iter.peek() {
-            Some(c) => c,
-            None => {
-                self.state = LexerState::Done;
-                return None
-            },
-        };
-
         self.state = LexerState::Lexing;
 
-        self.skip_whitespace();
+        let c = loop {
+            let start_line = self.iter.line();
+            let start_char = self.iter.char();
+            let start = self.iter.position();
 
-        if self.is_start_of_block_comment(c) {
-           self.parse_block_comment().err()?;
-           return None;
-        }
+            if self.trivia && self._peek().is_some_and(char::is_whitespace) {
+                self.skip_whitespace();
+                return Some(Ok(self.trivia_token(TokenKind::Whitespace, start_line, start_char, start)));
+            }
 
-        if self.is_start_of_line_comment(c) {
-            self.parse_line_comment().err()?;
-            return None;
-        }
+            self.skip_whitespace();
+
+            let c = match self.iter.peek() {
+                Some(c) => c,
+                None => {
+                    self.state = LexerState::Done;
+                    return None
+                },
+            };
+
+            if self.is_start_of_block_comment(c) {
+                if let Err(err) = self.parse_block_comment() {
+                    return Some(Err(err));
+                }
+                if self.trivia {
+                    return Some(Ok(self.trivia_token(TokenKind::BlockComment, start_line, start_char, start)));
+                }
+                continue;
+            }
+
+            if self.is_start_of_line_comment(c) {
+                if let Err(err) = self.parse_line_comment() {
+                    return Some(Err(err));
+                }
+                if self.trivia {
+                    return Some(Ok(self.trivia_token(TokenKind::LineComment, start_line, start_char, start)));
+                }
+                continue;
+            }
+
+            break c;
+        };
 
         if self.is_start_of_string(c) {
             return Some(self.parse_string());
@@ -111,28 +205,50 @@ impl<'a> Lexer<'a> {
         }
 
         if self.is_start_of_number(c) {
-            return Some(self.parse_number()?);
+            return self.parse_number();
         }
 
         if self.is_start_of_identifier(c) {
             return Some(Ok(self.parse_identifier()));
         }
 
+        let start_line = self.iter.line();
+        let start_char = self.iter.char();
+        let start = self.iter.position();
+
         let operator = self.parse_operator(c);
         if operator.is_none() {
-            return Some(Err(LexerError::from_location("Invalid operator".to_string(),
-                                               self.get_location())))
+            return Some(Err(LexerError::from_indices("Invalid operator".to_string(),
+                                               self.file(), start_line, start_char, self.iter.char())))
         }
 
+        let kind = operator.unwrap();
+
         return Some(Ok(Token {
-            kind: operator.unwrap(),
-            lexeme: "".to_string(),
-            line: self.iter.line(),
-            start_char: self.iter.char(),
+            kind,
+            file: self.file.id,
+            lexeme: kind.to_str().to_string(),
+            line: start_line,
+            start_char,
             end_char: self.iter.char(),
+            span: Span::new(start, self.iter.position()),
         }));
     }
 
+    fn trivia_token(&self, kind: TokenKind, line: usize, start_char: usize, start: usize) -> Token {
+        let span = Span::new(start, self.iter.position());
+
+        return Token {
+            kind,
+            file: self.file.id,
+            lexeme: self.file.slice(span).to_string(),
+            line,
+            start_char,
+            end_char: self.iter.char(),
+            span,
+        };
+    }
+
     fn skip_whitespace(&mut self) {
         while let Some(c) = self.iter.peek() {
             if !c.is_whitespace() {
@@ -150,11 +266,12 @@ impl<'a> Lexer<'a> {
     fn parse_identifier(&mut self) -> Token {
         let start_line = self.iter.line();
         let start_char = self.iter.char();
+        let start = self.iter.position();
 
         let mut buffer = String::new();
 
         while let Some(c) = self.iter.peek() {
-            if !self.is_start_of_identifier(c) && !c.is_digit(10) {
+            if !self.is_start_of_identifier(c) && !c.is_ascii_digit() {
                 break;
             }
 
@@ -163,22 +280,28 @@ impl<'a> Lexer<'a> {
 
         let end_char = self.iter.char();
 
+        let kind = TokenKind::from_str(&buffer)
+            .unwrap_or(TokenKind::Identifier);
+
         return Token {
-            kind: TokenKind::Identifier,
+            kind,
+            file: self.file.id,
             lexeme: buffer,
             line: start_line,
             start_char,
             end_char,
+            span: Span::new(start, self.iter.position()),
         };
     }
 
     fn is_start_of_number(&self, c: char) -> bool {
-        return c.is_digit(10);
+        return c.is_ascii_digit();
     }
 
     fn parse_number(&mut self) -> Option<Result<Token, LexerError>> {
         let start_line = self.iter.line();
         let start_char = self.iter.char();
+        let start = self.iter.position();
 
         let mut is_float = false;
 
@@ -190,13 +313,18 @@ impl<'a> Lexer<'a> {
             }
         }
 
-        while let Some(c) = self._next() {
+        while let Some(c) = self._peek() {
             match c {
                 '0'..='9' => {
                     buffer.push(c);
                 },
-                '_' => {continue;},
+                '_' => {},
                 '.' => {
+                    // `1..10` is a range, not a malformed float
+                    if !self._offset(1).is_some_and(|c| c.is_ascii_digit()) {
+                        break;
+                    }
+
                     if is_float {
                         return Some(Err(LexerError::from_location("Invalid float".to_string(),
                                                                 self.get_location())));
@@ -205,11 +333,14 @@ impl<'a> Lexer<'a> {
                     is_float = true;
                     buffer.push(c);
                 },
-                _ => {
+                _ if self.is_start_of_identifier(c) => {
                     return Some(Err(LexerError::from_location("Invalid number literal".to_string(),
                                                              self.get_location())));
                 }
+                _ => break,
             };
+
+            self._next();
         };
 
         let kind = if is_float {
@@ -218,12 +349,27 @@ impl<'a> Lexer<'a> {
             TokenKind::Integer
         };
 
+        // Integers are 64-bit signed. `-` is a token of its own, so the
+        // literal in `-9223372036854775808` is one past i64::MAX and the
+        // range can only be checked after negation; here only literals that
+        // can't fit either way are rejected.
+        let magnitude = i64::MIN.unsigned_abs() as i128;
+        if kind == TokenKind::Integer && !buffer.parse::<i128>().is_ok_and(|n| -magnitude <= n && n <= magnitude) {
+            return Some(Err(LexerError::from_indices("Integer literal is too large".to_string(),
+                                                     self.file(),
+                                                     start_line,
+                                                     start_char,
+                                                     self.iter.char())));
+        }
+
         return Some(Ok(Token {
             kind,
+            file: self.file.id,
             lexeme: buffer,
             line: start_line,
             start_char,
             end_char: self.iter.char(),
+            span: Span::new(start, self.iter.position()),
         }))
     }
 
@@ -235,15 +381,24 @@ impl<'a> Lexer<'a> {
         let mut string = String::new();
         let start_char = self.iter.char();
         let start_line = self.iter.line();
+        let start = self.iter.position();
 
         self._next(); // skip the starting '
 
-        let c = self._next().unwrap();
+        let unterminated = |lexer: &Self| {
+            let location = SourceCodeLocation::new(lexer.file(), start_line, start_char, lexer.iter.char());
+            return LexerError::unterminated("Unterminated char literal".to_string(), location);
+        };
+
+        let c = match self._next() {
+            Some(c) => c,
+            None => return Err(unterminated(self)),
+        };
 
         if c == '\\' {
             let next = match self._next() {
                 Some(c) => c,
-                None => return Err(LexerError::invalid_escape_sequence(self.get_location())),
+                None => return Err(unterminated(self)),
             };
 
             let resolved = match resolve_escape_sequence(next) {
@@ -256,11 +411,14 @@ impl<'a> Lexer<'a> {
             string.push(c);
         }
 
-        let next = self._next();
-        if next.is_none() || !self.is_start_of_char(next.unwrap()) {
+        let next = match self._next() {
+            Some(c) => c,
+            None => return Err(unterminated(self)),
+        };
+        if !self.is_start_of_char(next) {
             let end_char = self.iter.char();
             return Err(LexerError::from_indices("Invalid char".to_string(),
-                                                &self.text(),
+                                                self.file(),
                                                 start_line,
                                                 start_char,
                                                 end_char));
@@ -268,10 +426,12 @@ impl<'a> Lexer<'a> {
 
         return Ok(Token {
             kind: TokenKind::Char,
+            file: self.file.id,
             lexeme: string.clone(),
             line: self.iter.line(),
             start_char,
             end_char: self.iter.char(),
+            span: Span::new(start, self.iter.position()),
         });
     }
 
@@ -284,7 +444,9 @@ impl<'a> Lexer<'a> {
 
         let start_line = self.iter.line();
         let start_char = self.iter.char();
+        let start = self.iter.position();
         let mut terminated = false;
+        let mut invalid_escape = None;
 
         self._next(); // skip start of string
 
@@ -295,17 +457,23 @@ impl<'a> Lexer<'a> {
             }
 
             if c == '\\' {
+                // the escape may be a backslash before a line break, so
+                // take its position before reading on
+                let (escape_line, escape_char) = (self.iter.line(), self.iter.char() - 1);
                 let next = match self._next() {
                     Some(c) => c,
-                    None => return Err(LexerError::invalid_escape_sequence(self.get_location())),
+                    None => break,
                 };
 
-                let resolved = match resolve_escape_sequence(next) {
-                    Some(c) => c,
-                    None => return Err(LexerError::invalid_escape_sequence(self.get_location())),
-                };
-
-                string.push(resolved);
+                match resolve_escape_sequence(next) {
+                    Some(c) => string.push(c),
+                    // keep going to the closing quote so the rest of the
+                    // string isn't lexed as code
+                    None => {
+                        let location = SourceCodeLocation::new(self.file(), escape_line, escape_char, escape_char + 2);
+                        invalid_escape.get_or_insert(LexerError::invalid_escape_sequence(location));
+                    },
+                }
             } else {
                 string.push(c);
             }
@@ -313,19 +481,23 @@ impl<'a> Lexer<'a> {
 
         if !terminated {
             let end_char = self.iter.char();
-            return Err(LexerError::from_indices("Unterminated string literal".to_string(),
-                                                self.text(),
-                                                start_line,
-                                                start_char,
-                                                end_char));
+            let location = SourceCodeLocation::new(self.file(), start_line, start_char, end_char);
+            return Err(LexerError::unterminated("Unterminated string literal".to_string(),
+                                                location));
+        }
+
+        if let Some(err) = invalid_escape {
+            return Err(err);
         }
 
         return Ok(Token {
             kind: TokenKind::String,
+            file: self.file.id,
             lexeme: string.clone(),
             line: start_line,
             start_char,
             end_char: self.iter.char(),
+            span: Span::new(start, self.iter.position()),
         });
     }
 
@@ -334,10 +506,11 @@ impl<'a> Lexer<'a> {
     }
 
     fn parse_line_comment(&mut self) -> Result<(), LexerError> {
-        while let Some(c) = self._next() {
+        while let Some(c) = self._peek() {
             if c == '\n' {
                 break;
             }
+            self._next();
         }
         return Ok(());
     }
@@ -356,15 +529,15 @@ impl<'a> Lexer<'a> {
 
         let mut depth = 1;
 
-        while let Some(c) = self._next() {
+        while let Some(c) = self._peek() {
             if self.is_end_of_block_comment(c) {
-                self._next();
+                self._skip(2);
                 depth -= 1;
-            }
-
-            if self.is_start_of_block_comment(c) {
+            } else if self.is_start_of_block_comment(c) {
                 self._skip(2);
                 depth += 1;
+            } else {
+                self._next();
             }
 
             if depth == 0 {
@@ -372,7 +545,7 @@ impl<'a> Lexer<'a> {
             }
         }
 
-        return Err(LexerError::from_location(
+        return Err(LexerError::unterminated(
             "Unterminated block comment".to_string(),
             self.get_location()));
     }
@@ -380,11 +553,11 @@ impl<'a> Lexer<'a> {
     fn parse_operator(&mut self, c: char) -> Option<TokenKind> {
         self._next();
         let peek = self._peek();
+        let peek_next = self._offset(1);
 
-        return TokenKind::parse_operator(c, peek)
-            .and_then(|t| {
+        return TokenKind::parse_operator(c, peek, peek_next)
+            .inspect(|t| {
                 self._skip(t.to_str().len() - 1); // we skipped one already
-                Some(t)
             });
     }
 
@@ -408,13 +581,13 @@ impl<'a> Lexer<'a> {
         return self.iter.offset(num);
     }
 
-    fn text(&mut self) -> &String {
-        return self.iter.text();
+    fn file(&self) -> Rc<SourceFile> {
+        return Rc::clone(&self.file);
     }
 
     fn get_location(&self) -> SourceCodeLocation {
         return SourceCodeLocation {
-            text: self.iter.text().clone(),
+            file: self.file(),
             line: self.iter.line(),
             start_char: self.iter.char(),
             end_char: self.iter.char(),
@@ -424,8 +597,6 @@ impl<'a> Lexer<'a> {
 
 #[cfg(test)]
 mod lexer_tests {
-    use std::process::id;
-
     #[test]
     fn test_string_literal() {
         // given
@@ -468,6 +639,23 @@ mod lexer_tests {
         assert!(token.unwrap().is_err());
     }
 
+    #[test]
+    fn test_invalid_escape_recovers_after_string() {
+        // given
+        let code = String::from("\"a\\qb\" x");
+
+        // when
+        let mut lexer = super::Lexer::new(&code);
+        let err = lexer.next_token().unwrap().unwrap_err();
+        let next = lexer.next_token().unwrap().unwrap();
+
+        // then
+        let location = err.location().unwrap();
+        assert_eq!((location.start_char, location.end_char), (3, 5));
+        assert_eq!(next.lexeme, "x");
+        assert!(lexer.next_token().is_none());
+    }
+
     #[test]
     fn test_line_comment() {
         // given
@@ -525,6 +713,110 @@ mod lexer_tests {
         assert_eq!(token.kind, super::TokenKind::Slash);
     }
 
+    #[test]
+    fn test_parse_annotation() {
+        // given
+        let code = String::from("@allow(unused)");
+
+        // when
+        let mut lexer = super::Lexer::new(&code);
+        let mut kinds = Vec::new();
+        while let Some(token) = lexer.next_token() {
+            kinds.push(token.unwrap().kind);
+        }
+
+        // then
+        assert_eq!(kinds, [
+            super::TokenKind::At,
+            super::TokenKind::Identifier,
+            super::TokenKind::LeftParenthesis,
+            super::TokenKind::Identifier,
+            super::TokenKind::RightParenthesis,
+        ]);
+    }
+
+    #[test]
+    fn test_parse_null_safe_access() {
+        // given
+        let code = String::from("a?.b ?? c ? d:e?.5");
+
+        // when
+        let mut lexer = super::Lexer::new(&code);
+        let mut kinds = Vec::new();
+        while let Some(token) = lexer.next_token() {
+            kinds.push(token.unwrap().kind);
+        }
+
+        // then
+        assert_eq!(kinds, [
+            super::TokenKind::Identifier,
+            super::TokenKind::QuestionmarkDot,
+            super::TokenKind::Identifier,
+            super::TokenKind::QuestionmarkQuestionmark,
+            super::TokenKind::Identifier,
+            super::TokenKind::Questionmark,
+            super::TokenKind::Identifier,
+            super::TokenKind::Colon,
+            super::TokenKind::Identifier,
+            super::TokenKind::Questionmark,
+            super::TokenKind::Dot,
+            super::TokenKind::Integer,
+        ]);
+    }
+
+    #[test]
+    fn test_parse_dots() {
+        // given
+        let code = String::from("... .. .");
+        let expected = [
+            super::TokenKind::DotDotDot,
+            super::TokenKind::DotDot,
+            super::TokenKind::Dot,
+        ];
+
+        // when
+        let mut lexer = super::Lexer::new(&code);
+
+        for kind in expected {
+            let token = lexer.next_token().unwrap().unwrap();
+
+            // then
+            assert_eq!(token.kind, kind);
+        }
+    }
+
+    #[test]
+    fn test_parse_bitwise_and_arrows() {
+        // given
+        let code = String::from("& | ^ ~ << >> <= >= < > -> => -");
+        let expected = [
+            super::TokenKind::Ampersand,
+            super::TokenKind::Pipe,
+            super::TokenKind::Caret,
+            super::TokenKind::Tilde,
+            super::TokenKind::LessLess,
+            super::TokenKind::GreaterGreater,
+            super::TokenKind::LessEqual,
+            super::TokenKind::GreaterEqual,
+            super::TokenKind::Less,
+            super::TokenKind::Greater,
+            super::TokenKind::ThinArrow,
+            super::TokenKind::FatArrow,
+            super::TokenKind::Minus,
+        ];
+
+        // when
+        let mut lexer = super::Lexer::new(&code);
+
+        for kind in expected {
+            let token = lexer.next_token().unwrap().unwrap();
+
+            // then
+            assert_eq!(token.kind, kind);
+        }
+        assert!(lexer.next_token().is_none());
+    }
+
     #[test]
     fn test_parse_char() {
         // given
@@ -576,4 +868,244 @@ mod lexer_tests {
         }
 
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_multiple_tokens() {
+        // given
+        let code = String::from("let x = 12; // comment\n/* block */ x");
+        let expected = [
+            super::TokenKind::Let,
+            super::TokenKind::Identifier,
+            super::TokenKind::Equal,
+            super::TokenKind::Integer,
+            super::TokenKind::Semicolon,
+            super::TokenKind::Identifier,
+        ];
+
+        // when
+        let mut lexer = super::Lexer::new(&code);
+        let mut tokens = Vec::new();
+        while let Some(token) = lexer.next_token() {
+            tokens.push(token.unwrap());
+        }
+
+        // then
+        let kinds: Vec<super::TokenKind> = tokens.iter().map(|t| t.kind).collect();
+        assert_eq!(kinds, expected);
+        assert_eq!(tokens[3].lexeme, "12");
+        assert_eq!(tokens[4].start_char, 11);
+    }
+
+    #[test]
+    fn test_parse_integer_bounds() {
+        // given
+        let max = String::from("9_223_372_036_854_775_807");
+        let min_magnitude = String::from("-9223372036854775808");
+        let too_large = String::from("9223372036854775809");
+
+        // when
+        let max_token = super::Lexer::new(&max).next_token().unwrap();
+        let mut min_lexer = super::Lexer::new(&min_magnitude);
+        let minus = min_lexer.next_token().unwrap().unwrap();
+        let min_token = min_lexer.next_token().unwrap();
+        let too_large_token = super::Lexer::new(&too_large).next_token().unwrap();
+
+        // then
+        assert_eq!(max_token.unwrap().lexeme, i64::MAX.to_string());
+        assert_eq!(minus.kind, super::TokenKind::Minus);
+        assert_eq!(min_token.unwrap().lexeme, i64::MIN.unsigned_abs().to_string());
+        assert!(too_large_token.is_err());
+    }
+
+    #[test]
+    fn test_parse_integer_range() {
+        // given
+        let code = String::from("0..10");
+
+        // when
+        let mut lexer = super::Lexer::new(&code);
+        let start = lexer.next_token().unwrap().unwrap();
+        let dots = lexer.next_token().unwrap().unwrap();
+        let end = lexer.next_token().unwrap().unwrap();
+
+        // then
+        assert_eq!(start.kind, super::TokenKind::Integer);
+        assert_eq!(dots.kind, super::TokenKind::DotDot);
+        assert_eq!(end.lexeme, "10");
+    }
+
+    #[test]
+    fn test_parse_inclusive_range() {
+        // given
+        let code = String::from("0..=10");
+        let expected = [
+            super::TokenKind::Integer,
+            super::TokenKind::DotDotEqual,
+            super::TokenKind::Integer,
+        ];
+
+        // when
+        let mut lexer = super::Lexer::new(&code);
+
+        for kind in expected {
+            let token = lexer.next_token().unwrap().unwrap();
+
+            // then
+            assert_eq!(token.kind, kind);
+        }
+    }
+
+    #[test]
+    fn test_trivia_is_lossless() {
+        // given
+        let code = String::from("let s = \"a\\n\"; // comment\n/* block */\tx");
+
+        // when
+        let mut lexer = super::Lexer::new(&code).with_trivia();
+        let mut tokens = Vec::new();
+        while let Some(token) = lexer.next_token() {
+            tokens.push(token.unwrap());
+        }
+
+        // then
+        let text: String = tokens.iter().map(|t| &code[t.span.start..t.span.end]).collect();
+        assert_eq!(text, code);
+        assert!(tokens.iter().any(|t| t.kind == super::TokenKind::LineComment && t.lexeme == "// comment"));
+        assert!(tokens.iter().any(|t| t.kind == super::TokenKind::BlockComment));
+    }
+
+    #[test]
+    fn test_unterminated_block_comment() {
+        // given
+        let code = String::from("/* Hello, /* World! */");
+
+        // when
+        let mut lexer = super::Lexer::new(&code);
+        let token = lexer.next_token();
+
+        // then
+        assert!(token.unwrap().unwrap_err().is_unterminated());
+    }
+
+    #[test]
+    fn test_unterminated_char() {
+        for code in ["'", "'a", "'\\"] {
+            // given
+            let code = String::from(code);
+
+            // when
+            let mut lexer = super::Lexer::new(&code);
+            let token = lexer.next_token();
+
+            // then
+            assert!(token.unwrap().unwrap_err().is_unterminated(), "{}", code);
+        }
+    }
+
+    #[test]
+    fn test_non_ascii_text() {
+        // given
+        let code = String::from("\"ść\" ∑");
+
+        // when
+        let mut lexer = super::Lexer::new(&code);
+        let string = lexer.next_token().unwrap().unwrap();
+        let err = lexer.next_token().unwrap().unwrap_err();
+
+        // then
+        assert_eq!(string.lexeme, "ść");
+        assert_eq!(string.span, crate::source::Span::new(0, 6));
+        assert_eq!(string.end_char, 5);
+        assert_eq!(err.location().unwrap().start_char, 6);
+    }
+
+    #[test]
+    fn test_parse_keywords() {
+        // given
+        let keywords = [
+            ("try", super::TokenKind::Try),
+            ("catch", super::TokenKind::Catch),
+            ("finally", super::TokenKind::Finally),
+            ("throw", super::TokenKind::Throw),
+            ("match", super::TokenKind::Match),
+            ("yield", super::TokenKind::Yield),
+            ("do", super::TokenKind::Do),
+            ("switch", super::TokenKind::Switch),
+            ("case", super::TokenKind::Case),
+            ("default", super::TokenKind::Default),
+            ("interface", super::TokenKind::Interface),
+            ("is", super::TokenKind::Is),
+            ("static", super::TokenKind::Static),
+            ("defer", super::TokenKind::Defer),
+            ("with", super::TokenKind::With),
+            ("async", super::TokenKind::Async),
+            ("await", super::TokenKind::Await),
+        ];
+
+        for (keyword, kind) in keywords {
+            let code = String::from(keyword);
+
+            // when
+            let mut lexer = super::Lexer::new(&code);
+            let token = lexer.next_token().unwrap().unwrap();
+
+            // then
+            assert_eq!(token.kind, kind);
+            assert_eq!(token.lexeme, keyword);
+        }
+    }
+}
+
+#[cfg(test)]
+mod lexer_property_tests {
+    use proptest::prelude::*;
+    use crate::token::TokenKind;
+    use super::Lexer;
+
+    /// Lexes everything like `lang3 check` does, recovering after errors,
+    /// and checks the token spans are in order and inside the text.
+    fn lex_all(code: &String) {
+        let mut lexer = Lexer::new(code).with_trivia();
+        let mut position = lexer.position();
+        let mut end = 0;
+
+        while let Some(res) = lexer.next_token() {
+            if let Ok(token) = res {
+                assert!(end <= token.span.start && token.span.start <= token.span.end, "{:?} after {}", token, end);
+                assert!(token.span.end <= code.len() && code.is_char_boundary(token.span.end));
+                end = token.span.end;
+            } else if lexer.position() == position {
+                break;
+            }
+
+            assert!(lexer.position() <= code.len());
+            position = lexer.position();
+        }
+    }
+
+    /// Source-like text: mostly the characters the lexer cares about.
+    fn source() -> impl Strategy<Value = String> {
+        return prop::collection::vec(prop_oneof![
+            prop::sample::select(TokenKind::keywords().collect::<Vec<&str>>()).prop_map(String::from),
+            "[-+*/%=!<>&|^~?:;,.@(){}\\[\\]'\"\\\\ \t\r\n_a-z0-9]",
+            "\\PC",
+        ], 0..64).prop_map(|parts| parts.concat());
+    }
+
+    proptest! {
+        #[test]
+        fn test_lexer_never_panics_on_text(code in "\\PC*") {
+            lex_all(&code);
+        }
+
+        #[test]
+        fn test_lexer_never_panics_on_bytes(bytes in prop::collection::vec(any::<u8>(), 0..256)) {
+            lex_all(&String::from_utf8_lossy(&bytes).into_owned());
+        }
+
+        #[test]
+        fn test_lexer_never_panics_on_source(code in source()) {
+            lex_all(&code);
+        }
+    }
+}
@@ -0,0 +1,21 @@
+#![allow(clippy::needless_return)]
+
+pub mod cst;
+pub mod dependencies;
+pub mod diagnostics;
+pub mod doc;
+pub mod formatter;
+pub mod highlight;
+pub mod incremental;
+pub mod interner;
+pub mod iterator;
+pub mod lexer;
+pub mod lints;
+pub mod lsp;
+pub mod manifest;
+pub mod repl;
+pub mod source;
+pub mod symbols;
+pub mod token;
+pub mod util;
+pub mod watch;
@@ -0,0 +1,410 @@
+use std::rc::Rc;
+use crate::cst::{Cst, CstChild, CstKind, NodeId};
+use crate::diagnostics::{Annotations, Diagnostics, Level, Lint};
+use crate::source::SourceCodeLocation;
+use crate::symbols::{declarations, Declaration, DeclarationKind};
+use crate::token::{Token, TokenKind};
+
+/// A lint and the visitor that looks for it. `check_node` is called for
+/// every node of the syntax tree, parents before children; lints that need
+/// the whole file look at the root only.
+pub struct LintPass {
+    pub lint: &'static Lint,
+    pub check_node: fn(&mut LintContext, NodeId),
+}
+
+/// Every lint `lang3 check` runs. A new lint is a `Lint`, a visitor
+/// function and an entry here.
+pub const LINT_PASSES: &[LintPass] = &[
+    LintPass { lint: &UNUSED_VARIABLE, check_node: unused_variable },
+    LintPass { lint: &SHADOWED_VARIABLE, check_node: shadowed_variable },
+    LintPass { lint: &CONSTANT_CONDITION, check_node: constant_condition },
+    LintPass { lint: &NON_BOOLEAN_CONDITION, check_node: non_boolean_condition },
+    LintPass { lint: &ASSIGNMENT_IN_CONDITION, check_node: assignment_in_condition },
+    LintPass { lint: &EMPTY_BLOCK, check_node: empty_block },
+    LintPass { lint: &UNNECESSARY_COALESCE, check_node: unnecessary_coalesce },
+];
+
+pub const UNUSED_VARIABLE: Lint = Lint {
+    name: "unused_variable",
+    default: Level::Warn,
+    description: "a variable is declared but its name is never used",
+};
+
+pub const SHADOWED_VARIABLE: Lint = Lint {
+    name: "shadowed_variable",
+    default: Level::Allow,
+    description: "a variable is declared again while an earlier one of the same name is in scope",
+};
+
+pub const CONSTANT_CONDITION: Lint = Lint {
+    name: "constant_condition",
+    default: Level::Allow,
+    description: "an `if` or `while` condition is `true` or `false`",
+};
+
+pub const NON_BOOLEAN_CONDITION: Lint = Lint {
+    name: "non_boolean_condition",
+    default: Level::Warn,
+    description: "an `if` or `while` condition is a number, string, char or `null` literal",
+};
+
+pub const ASSIGNMENT_IN_CONDITION: Lint = Lint {
+    name: "assignment_in_condition",
+    default: Level::Warn,
+    description: "an `if` or `while` condition assigns with `=`, most likely meant to be `==`",
+};
+
+pub const EMPTY_BLOCK: Lint = Lint {
+    name: "empty_block",
+    default: Level::Allow,
+    description: "the block of an `if`, `else`, loop, `try` or `defer` is empty",
+};
+
+pub const UNNECESSARY_COALESCE: Lint = Lint {
+    name: "unnecessary_coalesce",
+    default: Level::Warn,
+    description: "the left side of `??` can never be null, so the right side is never used",
+};
+
+pub struct LintContext<'a> {
+    pub cst: &'a Cst,
+    annotations: &'a Annotations,
+    diagnostics: &'a mut Diagnostics,
+}
+
+impl LintContext<'_> {
+    pub fn report(&mut self, lint: &'static Lint, token: &Token, message: String) {
+        let location = SourceCodeLocation::new(Rc::clone(self.cst.file()), token.line, token.start_char, token.end_char);
+        self.diagnostics.lint(lint, self.annotations, token.span, message, location);
+    }
+
+    /// Children of a node without whitespace and comments.
+    pub fn significant(&self, node: NodeId) -> Vec<CstChild> {
+        return self.cst.node(node).children.iter()
+            .copied()
+            .filter(|c| !matches!(c, CstChild::Token(t) if self.cst.token(*t).kind.is_trivia()))
+            .collect();
+    }
+
+    /// The sibling right before a node, skipping whitespace and comments.
+    pub fn previous_sibling(&self, node: NodeId) -> Option<CstChild> {
+        let parent = self.cst.node(node).parent?;
+        let siblings = self.significant(parent);
+        let index = siblings.iter().position(|c| *c == CstChild::Node(node))?;
+
+        return index.checked_sub(1).map(|i| siblings[i]);
+    }
+
+    fn token_kind(&self, child: Option<CstChild>) -> Option<TokenKind> {
+        return match child {
+            Some(CstChild::Token(id)) => Some(self.cst.token(id).kind),
+            _ => None,
+        };
+    }
+
+    /// Whether a node is the parenthesized condition of an `if` or `while`.
+    fn is_condition(&self, node: NodeId) -> bool {
+        return self.cst.node(node).kind == CstKind::Parenthesized
+            && matches!(self.token_kind(self.previous_sibling(node)), Some(TokenKind::If | TokenKind::While));
+    }
+
+    fn ancestors(&self, node: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        return std::iter::successors(Some(node), |n| self.cst.node(*n).parent);
+    }
+}
+
+/// Runs every registered lint over a file that lexed without errors.
+pub fn run_lints(cst: &Cst, annotations: &Annotations, diagnostics: &mut Diagnostics) {
+    let mut cx = LintContext { cst, annotations, diagnostics };
+
+    for pass in LINT_PASSES {
+        let mut pending = vec![cst.root()];
+
+        while let Some(node) = pending.pop() {
+            (pass.check_node)(&mut cx, node);
+
+            for child in cst.node(node).children.iter().rev() {
+                if let CstChild::Node(id) = child {
+                    pending.push(*id);
+                }
+            }
+        }
+    }
+}
+
+fn variable_token<'a>(cst: &'a Cst, declaration: &Declaration) -> &'a Token {
+    return cst.token(cst.token_at(declaration.span.start).unwrap());
+}
+
+/// Reports `let` variables whose name never appears again in the file.
+/// Without scopes a use of any variable with the same name counts, so
+/// this only finds names that are certainly unused. Names starting with
+/// `_` are exempt.
+fn unused_variable(cx: &mut LintContext, node: NodeId) {
+    if node != cx.cst.root() {
+        return;
+    }
+
+    let tokens = cx.cst.tokens();
+    let declarations = declarations(tokens);
+    for declaration in &declarations {
+        let name = declarations.name(declaration);
+        if declaration.kind != DeclarationKind::Variable || name.starts_with('_') {
+            continue;
+        }
+
+        let used = tokens.iter().any(|t| {
+            return t.kind == TokenKind::Identifier && t.lexeme == name && t.span != declaration.span;
+        });
+        if !used {
+            let token = variable_token(cx.cst, declaration);
+            cx.report(&UNUSED_VARIABLE, token, format!("Unused variable '{}'", name));
+        }
+    }
+}
+
+/// Reports a `let` of a name already declared by a `let` or parameter in
+/// the same or an enclosing block.
+fn shadowed_variable(cx: &mut LintContext, node: NodeId) {
+    if node != cx.cst.root() {
+        return;
+    }
+
+    let table = declarations(cx.cst.tokens());
+    let declarations: Vec<(&Declaration, Option<NodeId>)> = table.iter()
+        .filter(|d| matches!(d.kind, DeclarationKind::Variable | DeclarationKind::Parameter))
+        .map(|d| (d, scope(cx, d)))
+        .collect();
+
+    for (i, (declaration, _)) in declarations.iter().enumerate() {
+        if declaration.kind != DeclarationKind::Variable {
+            continue;
+        }
+
+        let parent = cx.cst.token_parent(cx.cst.token_at(declaration.span.start).unwrap());
+        let shadowed = declarations[..i].iter().rev().find(|(earlier, scope)| {
+            return earlier.name == declaration.name
+                && scope.is_some_and(|scope| cx.ancestors(parent).any(|n| n == scope));
+        });
+
+        if let Some((earlier, _)) = shadowed {
+            let message = format!("Variable '{}' shadows the one declared on line {}", table.name(declaration), earlier.line);
+            let token = variable_token(cx.cst, declaration);
+            cx.report(&SHADOWED_VARIABLE, token, message);
+        }
+    }
+}
+
+/// Node a variable is visible in: the block holding a `let`, or the body
+/// following the parameter list of a parameter.
+fn scope(cx: &LintContext, declaration: &Declaration) -> Option<NodeId> {
+    let parent = cx.cst.token_parent(cx.cst.token_at(declaration.span.start)?);
+    if declaration.kind == DeclarationKind::Variable {
+        return Some(parent);
+    }
+
+    let siblings = cx.significant(cx.cst.node(parent).parent?);
+    let index = siblings.iter().position(|c| *c == CstChild::Node(parent))?;
+    return match siblings.get(index + 1) {
+        Some(CstChild::Node(body)) if cx.cst.node(*body).kind == CstKind::Block => Some(*body),
+        _ => None,
+    };
+}
+
+fn constant_condition(cx: &mut LintContext, node: NodeId) {
+    let Some(token) = condition_literal(cx, node) else { return };
+
+    let value = match token.kind {
+        TokenKind::True => true,
+        TokenKind::False => false,
+        _ => return,
+    };
+    cx.report(&CONSTANT_CONDITION, token, format!("Condition is always {}", value));
+}
+
+fn non_boolean_condition(cx: &mut LintContext, node: NodeId) {
+    let Some(token) = condition_literal(cx, node) else { return };
+
+    let what = match token.kind {
+        TokenKind::Integer | TokenKind::Float => "number",
+        TokenKind::String => "string",
+        TokenKind::Char => "char",
+        TokenKind::Null => "null",
+        _ => return,
+    };
+    cx.report(&NON_BOOLEAN_CONDITION, token, format!("Condition is a {} literal, not a boolean", what));
+}
+
+/// The literal an `if` or `while` condition consists of, if it is a
+/// single token.
+fn condition_literal<'a>(cx: &LintContext<'a>, node: NodeId) -> Option<&'a Token> {
+    if !cx.is_condition(node) {
+        return None;
+    }
+
+    let children = cx.significant(node);
+    let [_, CstChild::Token(id), _] = children[..] else { return None };
+    return Some(cx.cst.token(id));
+}
+
+fn assignment_in_condition(cx: &mut LintContext, node: NodeId) {
+    if !cx.is_condition(node) {
+        return;
+    }
+
+    for child in cx.significant(node) {
+        if let CstChild::Token(id) = child {
+            let token = cx.cst.token(id);
+            if token.kind == TokenKind::Equal {
+                cx.report(&ASSIGNMENT_IN_CONDITION, token, "Assignment in condition, did you mean '=='?".to_string());
+            }
+        }
+    }
+}
+
+/// Reports blocks with nothing but their braces after `if (...)`,
+/// `while (...)`, `for (...)`, `foreach (...)`, `catch (...)`, `else`,
+/// `do`, `try`, `finally` and `defer`. A comment inside counts as content, so an
+/// intentionally empty block can say why.
+fn empty_block(cx: &mut LintContext, node: NodeId) {
+    let block = cx.cst.node(node);
+    let empty = block.children.iter()
+        .all(|c| matches!(c, CstChild::Token(t) if matches!(cx.cst.token(*t).kind,
+            TokenKind::Whitespace | TokenKind::LeftBrace | TokenKind::RightBrace)));
+    if block.kind != CstKind::Block || !empty {
+        return;
+    }
+
+    let keyword = match cx.previous_sibling(node) {
+        Some(CstChild::Token(id)) => cx.cst.token(id).kind,
+        Some(CstChild::Node(paren)) if cx.cst.node(paren).kind == CstKind::Parenthesized => {
+            match cx.token_kind(cx.previous_sibling(paren)) {
+                Some(kind @ (TokenKind::If | TokenKind::While | TokenKind::For | TokenKind::Foreach | TokenKind::Catch)) => kind,
+                _ => return,
+            }
+        },
+        _ => return,
+    };
+    if !matches!(keyword, TokenKind::If | TokenKind::While | TokenKind::For | TokenKind::Foreach | TokenKind::Catch
+                          | TokenKind::Else | TokenKind::Do | TokenKind::Try | TokenKind::Finally | TokenKind::Defer) {
+        return;
+    }
+
+    let CstChild::Token(open) = block.children[0] else { return };
+    cx.report(&EMPTY_BLOCK, cx.cst.token(open), format!("Empty '{}' block", keyword));
+}
+
+/// Reports `??` right after a literal other than `null`. `??` binds looser
+/// than every other binary operator, so its left side then ends in a
+/// literal operand, as in `1` or `count + 1`, and can't be null.
+fn unnecessary_coalesce(cx: &mut LintContext, node: NodeId) {
+    let children = cx.significant(node);
+
+    for pair in children.windows(2) {
+        let [CstChild::Token(left), CstChild::Token(op)] = *pair else { continue };
+        let literal = matches!(cx.cst.token(left).kind,
+            TokenKind::Integer | TokenKind::Float | TokenKind::String | TokenKind::Char | TokenKind::True | TokenKind::False);
+
+        if literal && cx.cst.token(op).kind == TokenKind::QuestionmarkQuestionmark {
+            cx.report(&UNNECESSARY_COALESCE, cx.cst.token(op), "Left side of '??' can never be null".to_string());
+        }
+    }
+}
+
+#[cfg(test)]
+mod lints_tests {
+    use std::rc::Rc;
+    use crate::cst::Cst;
+    use crate::diagnostics::{Diagnostics, Level, LintConfig};
+    use crate::source::SourceFile;
+    use super::run_lints;
+
+    fn lint(code: &str, config: LintConfig) -> Vec<(usize, String)> {
+        let cst = Cst::parse(&Rc::new(SourceFile::anonymous(code.to_string()))).unwrap();
+        let mut diagnostics = Diagnostics::new(config);
+        let annotations = diagnostics.annotations(cst.file(), cst.tokens());
+
+        run_lints(&cst, &annotations, &mut diagnostics);
+
+        return diagnostics.diagnostics().iter()
+            .map(|d| (d.location.as_ref().unwrap().line, format!("{}: {}", d.lint.unwrap(), d.message)))
+            .collect();
+    }
+
+    #[test]
+    fn test_unused_variables() {
+        // given
+        let code = "let a = 1;\nlet b = 2;\nlet _c = 3;\nprint(b);\n@allow(unused_variable) let d = 4;";
+
+        // when
+        let found = lint(code, LintConfig::default());
+
+        // then
+        assert_eq!(found, [(1, "unused_variable: Unused variable 'a'".to_string())]);
+    }
+
+    #[test]
+    fn test_assignment_in_condition_warns_by_default() {
+        // given
+        let code = "let x = 1;\nif (x = 5) { print(x); }\nwhile (x == 5) { print(x); }";
+
+        // when
+        let found = lint(code, LintConfig::default());
+
+        // then
+        assert_eq!(found, [(2, "assignment_in_condition: Assignment in condition, did you mean '=='?".to_string())]);
+    }
+
+    #[test]
+    fn test_all_lints() {
+        // given
+        let mut config = LintConfig::default();
+        config.enable_all();
+        let code = "fn f(x) {
+    let x = 1;
+    if (x = 2) {}
+    while (true) { print(x); }
+    if (x) { let y = x; print(y); } else {
+        // nothing to do
+    }
+    try {} finally { print(1); }
+    print(x?.y ?? 1, x + 1 ?? 2, null ?? 3);
+}
+fn g() {}
+let x = 3;
+print(x);
+";
+
+        // when
+        let found = lint(code, config);
+
+        // then
+        let expected = [
+            (2, "shadowed_variable: Variable 'x' shadows the one declared on line 1"),
+            (4, "constant_condition: Condition is always true"),
+            (3, "assignment_in_condition: Assignment in condition, did you mean '=='?"),
+            (3, "empty_block: Empty 'if' block"),
+            (8, "empty_block: Empty 'try' block"),
+            (9, "unnecessary_coalesce: Left side of '??' can never be null"),
+        ];
+        assert_eq!(found, expected.map(|(line, message)| (line, message.to_string())));
+    }
+
+    #[test]
+    fn test_enable_all_keeps_explicit_levels() {
+        // given
+        let mut config = LintConfig::default();
+        config.set("empty_block", Level::Allow).unwrap();
+
+        // when
+        config.enable_all();
+        let found = lint("if (false) {}\nwhile (\"no\") { break; }", config);
+
+        // then
+        assert_eq!(found, [
+            (1, "constant_condition: Condition is always false".to_string()),
+            (2, "non_boolean_condition: Condition is a string literal, not a boolean".to_string()),
+        ]);
+    }
+}
@@ -0,0 +1,598 @@
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+use lsp_types::{
+    Diagnostic, DiagnosticSeverity, DidChangeTextDocumentParams, DidCloseTextDocumentParams,
+    DidOpenTextDocumentParams, DocumentSymbolParams, DocumentSymbolResponse,
+    GotoDefinitionParams, GotoDefinitionResponse, Hover, HoverContents, HoverParams,
+    HoverProviderCapability, InitializeResult, Location, MarkupContent, MarkupKind, NumberOrString, OneOf,
+    Position, PositionEncodingKind, PublishDiagnosticsParams, Range, SemanticToken,
+    SemanticTokenModifier, SemanticTokenType, SemanticTokens, SemanticTokensFullOptions,
+    SemanticTokensLegend, SemanticTokensOptions, SemanticTokensParams,
+    SemanticTokensServerCapabilities, ServerCapabilities, ServerInfo, SymbolInformation,
+    SymbolKind, TextDocumentContentChangeEvent, TextDocumentPositionParams,
+    TextDocumentSyncCapability, TextDocumentSyncKind, Url,
+};
+use serde_json::{json, Value};
+use crate::diagnostics::{Diagnostics, LintConfig, Severity};
+use crate::highlight::{highlight, HighlightKind};
+use crate::incremental::LexedFile;
+use crate::source::{SourceFile, Span};
+use crate::symbols::{declarations, Declaration, DeclarationKind, Declarations};
+use crate::token::{Token, TokenKind};
+
+const MAX_DIAGNOSTICS: usize = 100;
+
+/// Order of the semantic token legend; a token's type is its index here.
+const SEMANTIC_TOKEN_TYPES: [SemanticTokenType; 9] = [
+    SemanticTokenType::KEYWORD,
+    SemanticTokenType::FUNCTION,
+    SemanticTokenType::PARAMETER,
+    SemanticTokenType::VARIABLE,
+    SemanticTokenType::TYPE,
+    SemanticTokenType::STRING,
+    SemanticTokenType::NUMBER,
+    SemanticTokenType::COMMENT,
+    SemanticTokenType::OPERATOR,
+];
+
+/// Language server speaking JSON-RPC over any reader/writer pair, stdio
+/// in practice. Documents are synced incrementally and only re-lexed
+/// around each change.
+pub struct LanguageServer<R: BufRead, W: Write> {
+    reader: R,
+    writer: W,
+    documents: HashMap<Url, Document>,
+    encoding: PositionEncoding,
+    shutdown: bool,
+}
+
+/// What the `character` of a protocol position counts: UTF-16 code units
+/// unless the client offers Unicode scalar values in `initialize`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PositionEncoding {
+    Utf16,
+    Utf32,
+}
+
+impl PositionEncoding {
+    fn negotiate(params: &Value) -> Self {
+        let offered = params["capabilities"]["general"]["positionEncodings"].as_array();
+        let utf32 = offered.is_some_and(|kinds| kinds.iter().any(|k| k == PositionEncodingKind::UTF32.as_str()));
+        return if utf32 { PositionEncoding::Utf32 } else { PositionEncoding::Utf16 };
+    }
+
+    fn kind(self) -> PositionEncodingKind {
+        return match self {
+            PositionEncoding::Utf16 => PositionEncodingKind::UTF16,
+            PositionEncoding::Utf32 => PositionEncodingKind::UTF32,
+        };
+    }
+
+    fn width(self, text: &str) -> usize {
+        return match self {
+            PositionEncoding::Utf16 => text.encode_utf16().count(),
+            PositionEncoding::Utf32 => text.chars().count(),
+        };
+    }
+}
+
+/// An open document with its lossless token stream. Lex errors are the
+/// stream's `Invalid` tokens.
+struct Document {
+    lexed: LexedFile,
+    declarations: Declarations,
+}
+
+impl Document {
+    fn new(uri: &Url, text: String) -> Self {
+        let mut file = SourceFile::anonymous(text);
+        file.path = PathBuf::from(uri.path());
+        let lexed = LexedFile::new(file);
+        let declarations = declarations(lexed.tokens());
+
+        return Document { lexed, declarations };
+    }
+
+    fn file(&self) -> &SourceFile {
+        return self.lexed.file();
+    }
+
+    /// Applies a `didChange` edit, or replaces the whole text if it has no
+    /// range.
+    fn apply_change(&mut self, change: TextDocumentContentChangeEvent, encoding: PositionEncoding) {
+        match change.range {
+            Some(range) => {
+                let end_of_file = self.file().text.len();
+                let start = self.offset(range.start, encoding).unwrap_or(end_of_file);
+                let end = self.offset(range.end, encoding).unwrap_or(end_of_file).max(start);
+                self.lexed.apply_edit(Span::new(start, end), &change.text);
+            },
+            None => {
+                let mut file = SourceFile::anonymous(change.text);
+                file.path = self.file().path.clone();
+                self.lexed = LexedFile::new(file);
+            },
+        }
+
+        self.declarations = declarations(self.lexed.tokens());
+    }
+
+    /// Protocol position of a 1-based line and character column, the way
+    /// tokens and errors count them.
+    fn position(&self, line: usize, char: usize, encoding: PositionEncoding) -> Position {
+        let text = self.file().line(line);
+        let end = text.char_indices().nth(char.saturating_sub(1)).map_or(text.len(), |(i, _)| i);
+        return Position::new(line.saturating_sub(1) as u32, encoding.width(&text[..end]) as u32);
+    }
+
+    fn range(&self, line: usize, start_char: usize, end_char: usize, encoding: PositionEncoding) -> Range {
+        return Range::new(self.position(line, start_char, encoding), self.position(line, end_char, encoding));
+    }
+
+    fn declaration_range(&self, declaration: &Declaration, encoding: PositionEncoding) -> Range {
+        return self.range(declaration.line, declaration.start_char, declaration.end_char, encoding);
+    }
+
+    /// Byte offset of a protocol position. Characters past the end of the
+    /// line mean the end of the line.
+    fn offset(&self, position: Position, encoding: PositionEncoding) -> Option<usize> {
+        let file = self.file();
+        let span = file.line_index().line_span(&file.text, position.line as usize)?;
+        let text = file.slice(span);
+        let offset = text.char_indices()
+            .find(|(i, _)| encoding.width(&text[..*i]) >= position.character as usize)
+            .map_or(span.end, |(i, _)| span.start + i);
+
+        return Some(offset);
+    }
+
+    fn identifier_at(&self, position: Position, encoding: PositionEncoding) -> Option<&Token> {
+        let offset = self.offset(position, encoding)?;
+
+        return self.lexed.tokens().iter()
+            .filter(|t| t.kind == TokenKind::Identifier)
+            .find(|t| t.span.start <= offset && offset <= t.span.end);
+    }
+
+    fn declaration_at(&self, position: Position, encoding: PositionEncoding) -> Option<&Declaration> {
+        let token = self.identifier_at(position, encoding)?;
+        return self.declarations.find(&token.lexeme, token.span.start);
+    }
+}
+
+impl<R: BufRead, W: Write> LanguageServer<R, W> {
+    pub fn new(reader: R, writer: W) -> Self {
+        return LanguageServer {
+            reader,
+            writer,
+            documents: HashMap::new(),
+            encoding: PositionEncoding::Utf16,
+            shutdown: false,
+        };
+    }
+
+    /// Serves requests until the client sends `exit` or closes the stream.
+    /// Returns the process exit code the protocol asks for.
+    pub fn run(&mut self) -> i32 {
+        while let Some(message) = self.read_message() {
+            let method = message["method"].as_str().unwrap_or("").to_string();
+            let params = message["params"].clone();
+
+            if method == "exit" {
+                return if self.shutdown { 0 } else { 1 };
+            }
+
+            match message.get("id").cloned() {
+                Some(id) => {
+                    let response = match self.handle_request(&method, params) {
+                        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                        Err((code, msg)) => json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": msg } }),
+                    };
+                    self.write_message(&response);
+                },
+                None => self.handle_notification(&method, params),
+            }
+        }
+
+        return 1;
+    }
+
+    fn handle_request(&mut self, method: &str, params: Value) -> Result<Value, (i64, String)> {
+        return match method {
+            "initialize" => Ok(json!(self.initialize(&params))),
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Value::Null)
+            },
+            "textDocument/documentSymbol" => {
+                let params: DocumentSymbolParams = parse_params(params)?;
+                Ok(json!(self.document_symbols(&params.text_document.uri)))
+            },
+            "textDocument/definition" => {
+                let params: GotoDefinitionParams = parse_params(params)?;
+                Ok(json!(self.definition(&params.text_document_position_params)))
+            },
+            "textDocument/hover" => {
+                let params: HoverParams = parse_params(params)?;
+                Ok(json!(self.hover(&params.text_document_position_params)))
+            },
+            "textDocument/semanticTokens/full" => {
+                let params: SemanticTokensParams = parse_params(params)?;
+                Ok(json!(self.semantic_tokens(&params.text_document.uri)))
+            },
+            _ => Err((-32601, format!("Method not found: {}", method))),
+        };
+    }
+
+    /// Agrees on a position encoding and announces what the server can do.
+    fn initialize(&mut self, params: &Value) -> InitializeResult {
+        self.encoding = PositionEncoding::negotiate(params);
+
+        return InitializeResult {
+            capabilities: ServerCapabilities {
+                position_encoding: Some(self.encoding.kind()),
+                text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::INCREMENTAL)),
+                hover_provider: Some(HoverProviderCapability::Simple(true)),
+                definition_provider: Some(OneOf::Left(true)),
+                document_symbol_provider: Some(OneOf::Left(true)),
+                semantic_tokens_provider: Some(SemanticTokensServerCapabilities::SemanticTokensOptions(SemanticTokensOptions {
+                    legend: SemanticTokensLegend {
+                        token_types: SEMANTIC_TOKEN_TYPES.to_vec(),
+                        token_modifiers: vec![SemanticTokenModifier::READONLY],
+                    },
+                    full: Some(SemanticTokensFullOptions::Bool(true)),
+                    ..SemanticTokensOptions::default()
+                })),
+                ..ServerCapabilities::default()
+            },
+            server_info: Some(ServerInfo {
+                name: "lang3".to_string(),
+                version: Some(env!("CARGO_PKG_VERSION").to_string()),
+            }),
+        };
+    }
+
+    fn handle_notification(&mut self, method: &str, params: Value) {
+        match method {
+            "textDocument/didOpen" => {
+                if let Ok(params) = parse_params::<DidOpenTextDocumentParams>(params) {
+                    let uri = params.text_document.uri;
+                    self.documents.insert(uri.clone(), Document::new(&uri, params.text_document.text));
+                    self.publish_check(uri);
+                }
+            },
+            "textDocument/didChange" => {
+                if let Ok(params) = parse_params::<DidChangeTextDocumentParams>(params) {
+                    let uri = params.text_document.uri;
+                    let Some(document) = self.documents.get_mut(&uri) else { return };
+                    for change in params.content_changes {
+                        document.apply_change(change, self.encoding);
+                    }
+                    self.publish_check(uri);
+                }
+            },
+            "textDocument/didClose" => {
+                if let Ok(params) = parse_params::<DidCloseTextDocumentParams>(params) {
+                    self.documents.remove(&params.text_document.uri);
+                    self.publish_diagnostics(params.text_document.uri, Vec::new());
+                }
+            },
+            _ => {},
+        }
+    }
+
+    /// Publishes what `lang3 check` reports for the document: its lex
+    /// errors and, once it lexes, the lints at their default levels with
+    /// the document's own annotations applied.
+    fn publish_check(&mut self, uri: Url) {
+        let Some(document) = self.documents.get(&uri) else { return };
+        let mut checked = Diagnostics::new(LintConfig::default());
+        checked.check_file(document.lexed.file());
+
+        let diagnostics = checked.diagnostics().iter()
+            .take(MAX_DIAGNOSTICS)
+            .map(|d| Diagnostic {
+                range: d.location.as_ref()
+                    .map_or(Range::default(), |l| document.range(l.line, l.start_char, l.end_char, self.encoding)),
+                severity: Some(match d.severity {
+                    Severity::Error => DiagnosticSeverity::ERROR,
+                    Severity::Warning => DiagnosticSeverity::WARNING,
+                }),
+                code: d.lint.map(|lint| NumberOrString::String(lint.to_string())),
+                source: Some("lang3".to_string()),
+                message: d.message.clone(),
+                ..Diagnostic::default()
+            })
+            .collect();
+
+        self.publish_diagnostics(uri, diagnostics);
+    }
+
+    fn publish_diagnostics(&mut self, uri: Url, diagnostics: Vec<Diagnostic>) {
+        let params = PublishDiagnosticsParams { uri, diagnostics, version: None };
+        self.write_message(&json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": params,
+        }));
+    }
+
+    #[allow(deprecated)]
+    fn document_symbols(&self, uri: &Url) -> Option<DocumentSymbolResponse> {
+        let document = self.documents.get(uri)?;
+        let symbols = document.declarations.iter()
+            .filter(|d| d.kind != DeclarationKind::Parameter)
+            .map(|d| SymbolInformation {
+                name: document.declarations.name(d).to_string(),
+                kind: symbol_kind(d.kind),
+                tags: None,
+                deprecated: None,
+                location: Location::new(uri.clone(), document.declaration_range(d, self.encoding)),
+                container_name: None,
+            })
+            .collect();
+
+        return Some(DocumentSymbolResponse::Flat(symbols));
+    }
+
+    fn definition(&self, params: &TextDocumentPositionParams) -> Option<GotoDefinitionResponse> {
+        let uri = &params.text_document.uri;
+        let document = self.documents.get(uri)?;
+        let declaration = document.declaration_at(params.position, self.encoding)?;
+
+        return Some(GotoDefinitionResponse::Scalar(Location::new(uri.clone(), document.declaration_range(declaration, self.encoding))));
+    }
+
+    fn hover(&self, params: &TextDocumentPositionParams) -> Option<Hover> {
+        let document = self.documents.get(&params.text_document.uri)?;
+        let declaration = document.declaration_at(params.position, self.encoding)?;
+        let line = document.file().line(declaration.line).trim();
+
+        return Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: format!("{} `{}`\n\n```lang3\n{}\n```", describe(declaration.kind), document.declarations.name(declaration), line),
+            }),
+            range: None,
+        });
+    }
+
+    /// Highlighting for the whole document, relative encoded as the
+    /// protocol wants it. Tokens spanning several lines are left out since
+    /// not every client supports them.
+    fn semantic_tokens(&self, uri: &Url) -> Option<SemanticTokens> {
+        let document = self.documents.get(uri)?;
+        let tokens = document.lexed.tokens();
+        let mut data = Vec::new();
+        let mut previous = Position::new(0, 0);
+
+        for (token, kind) in tokens.iter().zip(highlight(tokens)) {
+            let text = document.file().slice(token.span);
+            let Some((token_type, modifiers)) = semantic_token_type(kind) else { continue };
+            if text.contains('\n') {
+                continue;
+            }
+
+            let position = document.position(token.line, token.start_char, self.encoding);
+            if position.line != previous.line {
+                previous.character = 0;
+            }
+
+            data.push(SemanticToken {
+                delta_line: position.line - previous.line,
+                delta_start: position.character - previous.character,
+                length: self.encoding.width(text) as u32,
+                token_type,
+                token_modifiers_bitset: modifiers,
+            });
+
+            previous = position;
+        }
+
+        return Some(SemanticTokens { result_id: None, data });
+    }
+
+    fn read_message(&mut self) -> Option<Value> {
+        let mut length = None;
+
+        loop {
+            let mut header = String::new();
+            if self.reader.read_line(&mut header).ok()? == 0 {
+                return None;
+            }
+
+            let header = header.trim_end();
+            if header.is_empty() {
+                break;
+            }
+
+            if let Some(value) = header.strip_prefix("Content-Length:") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+
+        let mut body = vec![0; length?];
+        self.reader.read_exact(&mut body).ok()?;
+
+        return serde_json::from_slice(&body).ok();
+    }
+
+    fn write_message(&mut self, message: &Value) {
+        let body = message.to_string();
+        let _ = write!(self.writer, "Content-Length: {}\r\n\r\n{}", body.len(), body);
+        let _ = self.writer.flush();
+    }
+}
+
+fn parse_params<T: serde::de::DeserializeOwned>(params: Value) -> Result<T, (i64, String)> {
+    return serde_json::from_value(params).map_err(|err| (-32602, err.to_string()));
+}
+
+fn symbol_kind(kind: DeclarationKind) -> SymbolKind {
+    return match kind {
+        DeclarationKind::Function => SymbolKind::FUNCTION,
+        DeclarationKind::Parameter | DeclarationKind::Variable => SymbolKind::VARIABLE,
+        DeclarationKind::Constant => SymbolKind::CONSTANT,
+        DeclarationKind::Class => SymbolKind::CLASS,
+        DeclarationKind::Struct => SymbolKind::STRUCT,
+        DeclarationKind::Interface => SymbolKind::INTERFACE,
+    };
+}
+
+fn semantic_token_type(kind: HighlightKind) -> Option<(u32, u32)> {
+    let token_type = match kind {
+        HighlightKind::Keyword => SemanticTokenType::KEYWORD,
+        HighlightKind::Function => SemanticTokenType::FUNCTION,
+        HighlightKind::Parameter => SemanticTokenType::PARAMETER,
+        HighlightKind::Variable | HighlightKind::Constant => SemanticTokenType::VARIABLE,
+        HighlightKind::Type => SemanticTokenType::TYPE,
+        HighlightKind::String => SemanticTokenType::STRING,
+        HighlightKind::Number => SemanticTokenType::NUMBER,
+        HighlightKind::Comment => SemanticTokenType::COMMENT,
+        HighlightKind::Operator => SemanticTokenType::OPERATOR,
+        HighlightKind::Plain => return None,
+    };
+    let index = SEMANTIC_TOKEN_TYPES.iter().position(|t| *t == token_type)? as u32;
+    let modifiers = if kind == HighlightKind::Constant { 1 } else { 0 };
+
+    return Some((index, modifiers));
+}
+
+fn describe(kind: DeclarationKind) -> &'static str {
+    return match kind {
+        DeclarationKind::Function => "function",
+        DeclarationKind::Parameter => "parameter",
+        DeclarationKind::Variable => "variable",
+        DeclarationKind::Constant => "constant",
+        DeclarationKind::Class => "class",
+        DeclarationKind::Struct => "struct",
+        DeclarationKind::Interface => "interface",
+    };
+}
+
+#[cfg(test)]
+mod lsp_tests {
+    use std::io::Cursor;
+    use serde_json::{json, Value};
+    use super::LanguageServer;
+
+    fn frame(messages: &[Value]) -> Vec<u8> {
+        let mut input = String::new();
+        for message in messages {
+            let body = message.to_string();
+            input.push_str(&format!("Content-Length: {}\r\n\r\n{}", body.len(), body));
+        }
+        return input.into_bytes();
+    }
+
+    fn responses(output: &[u8]) -> Vec<Value> {
+        let output = String::from_utf8(output.to_vec()).unwrap();
+        return output.split("Content-Length: ")
+            .filter(|m| !m.is_empty())
+            .map(|m| serde_json::from_str(m.split_once("\r\n\r\n").unwrap().1).unwrap())
+            .collect();
+    }
+
+    #[test]
+    fn test_session() {
+        // given
+        let uri = "file:///tmp/main.l3";
+        let position = |line: u32, character: u32| json!({
+            "textDocument": { "uri": uri },
+            "position": { "line": line, "character": character },
+        });
+        let input = frame(&[
+            json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": { "capabilities": {} } }),
+            json!({ "jsonrpc": "2.0", "method": "textDocument/didOpen", "params": { "textDocument": {
+                "uri": uri, "languageId": "lang3", "version": 1,
+                "text": "fn add(a, b) {\n    return a + b;\n}\nlet s = \"open",
+            } } }),
+            json!({ "jsonrpc": "2.0", "method": "textDocument/didChange", "params": {
+                "textDocument": { "uri": uri, "version": 2 },
+                "contentChanges": [
+                    { "range": { "start": { "line": 0, "character": 3 }, "end": { "line": 0, "character": 6 } }, "text": "sum" },
+                    { "range": { "start": { "line": 3, "character": 13 }, "end": { "line": 3, "character": 13 } }, "text": "\";" },
+                ],
+            } }),
+            json!({ "jsonrpc": "2.0", "id": 2, "method": "textDocument/documentSymbol", "params": { "textDocument": { "uri": uri } } }),
+            json!({ "jsonrpc": "2.0", "id": 3, "method": "textDocument/definition", "params": position(1, 11) }),
+            json!({ "jsonrpc": "2.0", "id": 4, "method": "textDocument/hover", "params": position(1, 15) }),
+            json!({ "jsonrpc": "2.0", "id": 5, "method": "textDocument/semanticTokens/full", "params": { "textDocument": { "uri": uri } } }),
+            json!({ "jsonrpc": "2.0", "id": 6, "method": "shutdown" }),
+            json!({ "jsonrpc": "2.0", "method": "exit" }),
+        ]);
+        let mut output = Vec::new();
+
+        // when
+        let code = LanguageServer::new(Cursor::new(input), &mut output).run();
+
+        // then
+        assert_eq!(code, 0);
+        let responses = responses(&output);
+
+        assert_eq!(responses[0]["result"]["capabilities"]["definitionProvider"], true);
+        assert_eq!(responses[0]["result"]["capabilities"]["textDocumentSync"], 2);
+
+        let diagnostics = &responses[1]["params"]["diagnostics"];
+        assert_eq!(diagnostics[0]["message"], "Unterminated string literal");
+        assert_eq!(diagnostics[0]["range"]["start"]["line"], 3);
+        let diagnostics = &responses[2]["params"]["diagnostics"];
+        assert_eq!(diagnostics.as_array().unwrap().len(), 1);
+        assert_eq!(diagnostics[0]["message"], "Unused variable 's'");
+        assert_eq!(diagnostics[0]["code"], "unused_variable");
+        assert_eq!(diagnostics[0]["severity"], 2);
+        assert_eq!(diagnostics[0]["range"]["start"], json!({ "line": 3, "character": 4 }));
+
+        let symbols: Vec<&str> = responses[3]["result"].as_array().unwrap().iter()
+            .map(|s| s["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(symbols, ["sum", "s"]);
+
+        assert_eq!(responses[4]["result"]["range"]["start"], json!({ "line": 0, "character": 7 }));
+        assert!(responses[5]["result"]["contents"]["value"].as_str().unwrap().starts_with("parameter `b`"));
+
+        // `fn` at 0:0, `sum` at 0:3, `(` at 0:6 and `a` at 0:7
+        let data: Vec<u64> = responses[6]["result"]["data"].as_array().unwrap().iter()
+            .map(|v| v.as_u64().unwrap())
+            .collect();
+        assert_eq!(&data[..20], [0, 0, 2, 0, 0, 0, 3, 3, 1, 0, 0, 3, 1, 8, 0, 0, 1, 1, 2, 0]);
+
+        assert_eq!(responses[7]["result"], Value::Null);
+    }
+
+    #[test]
+    fn test_position_encoding() {
+        // given
+        let uri = "file:///tmp/emoji.l3";
+        let session = |capabilities: Value, definition: u32| frame(&[
+            json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": { "capabilities": capabilities } }),
+            json!({ "jsonrpc": "2.0", "method": "textDocument/didOpen", "params": { "textDocument": {
+                "uri": uri, "languageId": "lang3", "version": 1,
+                "text": "let s = \"\u{1F600}\"; let t = s;\n\"\u{1F600}\" + \"open",
+            } } }),
+            json!({ "jsonrpc": "2.0", "id": 2, "method": "textDocument/definition", "params": {
+                "textDocument": { "uri": uri },
+                "position": { "line": 0, "character": definition },
+            } }),
+        ]);
+        let utf32 = json!({ "general": { "positionEncodings": ["utf-32", "utf-16"] } });
+        let mut utf16_output = Vec::new();
+        let mut utf32_output = Vec::new();
+
+        // when
+        LanguageServer::new(Cursor::new(session(json!({}), 22)), &mut utf16_output).run();
+        LanguageServer::new(Cursor::new(session(utf32, 21)), &mut utf32_output).run();
+
+        // then
+        let utf16 = responses(&utf16_output);
+        assert_eq!(utf16[0]["result"]["capabilities"]["positionEncoding"], "utf-16");
+        assert_eq!(utf16[1]["params"]["diagnostics"][0]["range"]["start"], json!({ "line": 1, "character": 7 }));
+        assert_eq!(utf16[2]["result"]["range"]["start"], json!({ "line": 0, "character": 4 }));
+
+        let utf32 = responses(&utf32_output);
+        assert_eq!(utf32[0]["result"]["capabilities"]["positionEncoding"], "utf-32");
+        assert_eq!(utf32[1]["params"]["diagnostics"][0]["range"]["start"], json!({ "line": 1, "character": 6 }));
+        assert_eq!(utf32[2]["result"]["range"]["start"], json!({ "line": 0, "character": 4 }));
+    }
+}
@@ -1,35 +1,518 @@
-use std::env;
-use crate::lexer::Lexer;
+#![allow(clippy::needless_return)]
 
-mod iterator;
-mod lexer;
-mod token;
-mod util;
-mod source;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::exit;
+use lang3::dependencies::{add_dependency, env_module_paths, module_candidates, resolve_locked, resolve_module, ResolvedPackage, LOCKFILE_NAME};
+use lang3::diagnostics::{Diagnostics, Level, LintConfig, Severity};
+use lang3::doc::{document, render as render_doc, DocFormat};
+use lang3::formatter::format_source;
+use lang3::highlight::{render, HighlightFormat};
+use lang3::incremental::LexedFile;
+use lang3::lexer::Lexer;
+use lang3::lsp::LanguageServer;
+use lang3::manifest::{Manifest, MANIFEST_NAME};
+use lang3::repl::Repl;
+use lang3::source::SourceMap;
+use lang3::token::Token;
+use lang3::watch::Watcher;
 
-use crate::token::Token;
+/// Exit status when the input has errors or a file can't be read or written.
+const EXIT_ERROR: i32 = 1;
+/// Exit status for a malformed command line.
+const EXIT_USAGE: i32 = 2;
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let mut args: Vec<String> = env::args().collect();
+
+    if !set_color(&mut args) {
+        exit(EXIT_USAGE);
+    }
 
     if args.len() < 2 {
-        println!("Usage: {} <file>", args[0]);
-        return;
+        println!("Usage: {} <file>...", args[0]);
+        println!("       {} repl", args[0]);
+        println!("       {} check [--watch] [--lint] [-A|-W|-D <lint>] [--deny-warnings] [<file>...]", args[0]);
+        println!("       {} check --print-module-resolution <module>", args[0]);
+        println!("       {} fmt [--check] [<file>...]", args[0]);
+        println!("       {} highlight [--format=html|ansi] [<file>...]", args[0]);
+        println!("       {} doc [--format=markdown|html] [--out=<dir>] [<file>...]", args[0]);
+        println!("       {} lsp", args[0]);
+        println!("       {} add <name> --path <dir>", args[0]);
+        println!();
+        println!("Options: --color=auto|always|never  colored output and diagnostics");
+        println!();
+        println!("Subcommands given no files use the sources of the {} project", MANIFEST_NAME);
+        println!("found in the current directory or one of its parents.");
+        println!();
+        println!("Exit status: 0 on success, {} if the input has errors, {} for a bad command line.", EXIT_ERROR, EXIT_USAGE);
+        exit(EXIT_USAGE);
+    }
+
+    match args[1].as_str() {
+        "repl" => Repl::new().run(),
+        "check" => exit(check(&args[2..])),
+        "fmt" => exit(fmt(&args[2..])),
+        "highlight" => exit(highlight(&args[2..])),
+        "doc" => exit(doc(&args[2..])),
+        "lsp" => exit(LanguageServer::new(std::io::stdin().lock(), std::io::stdout()).run()),
+        "add" => exit(add(&args[2..])),
+        arg if is_option(arg) => exit(unknown_option(arg)),
+        arg if !looks_like_file(arg) => {
+            eprintln!("Unknown subcommand '{}', run {} without arguments for usage", arg, args[0]);
+            exit(EXIT_USAGE);
+        },
+        _ => exit(lex(&args[1..])),
+    }
+}
+
+/// Whether an argument of `lang3 <file>...` is meant as a file rather
+/// than a misspelled subcommand: it exists, has an extension or is a path.
+fn looks_like_file(arg: &str) -> bool {
+    let path = Path::new(arg);
+    return path.exists() || path.extension().is_some() || path.components().count() > 1;
+}
+
+fn is_option(arg: &str) -> bool {
+    return arg.starts_with('-') && arg != "-";
+}
+
+fn unknown_option(arg: &str) -> i32 {
+    eprintln!("Unknown option '{}'", arg);
+    return EXIT_USAGE;
+}
+
+/// Applies `--color=auto|always|never` wherever it appears and removes it
+/// from the arguments. Returns false for an unknown value.
+fn set_color(args: &mut Vec<String>) -> bool {
+    let mut valid = true;
+
+    args.retain(|arg| {
+        match arg.strip_prefix("--color=") {
+            Some("always") => colored::control::set_override(true),
+            Some("never") => colored::control::set_override(false),
+            Some("auto") => colored::control::unset_override(),
+            Some(other) => {
+                eprintln!("Unknown color mode '{}', expected 'auto', 'always' or 'never'", other);
+                valid = false;
+            },
+            None => return true,
+        }
+        return false;
+    });
+
+    return valid;
+}
+
+/// The files named on the command line or, if there are none, the source
+/// files of the project the current directory belongs to.
+fn input_files(paths: &[String]) -> Option<Vec<String>> {
+    if !paths.is_empty() {
+        return Some(paths.to_vec());
+    }
+
+    let dir = env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+    return match Manifest::discover(&dir) {
+        Ok(Some(manifest)) => Some(manifest.source_files().iter()
+            .map(|p| p.display().to_string())
+            .collect()),
+        Ok(None) => {
+            eprintln!("No input files given and no {} found", MANIFEST_NAME);
+            None
+        },
+        Err(err) => {
+            eprintln!("{}", err);
+            None
+        },
+    };
+}
+
+fn load_files(paths: &[String]) -> Option<SourceMap> {
+    let mut source_map = SourceMap::new();
+
+    for path in &input_files(paths)? {
+        if let Err(err) = source_map.load_file(Path::new(path)) {
+            eprintln!("Failed to read file '{}': {}", path, err);
+            return None;
+        }
+    }
+
+    return Some(source_map);
+}
+
+fn lex(paths: &[String]) -> i32 {
+    if let Some(option) = paths.iter().find(|p| is_option(p)) {
+        return unknown_option(option);
+    }
+
+    let source_map = match load_files(paths) {
+        Some(source_map) => source_map,
+        None => return EXIT_ERROR,
+    };
+
+    let mut code = 0;
+
+    for file in source_map.files() {
+        let mut lexer = Lexer::from_source_file(file);
+        let mut tokens = Vec::<Token>::new();
+
+        while let Some(res) = lexer.next_token() {
+            if res.is_err() {
+                let err = res.err().unwrap();
+                println!("{}", err);
+                code = EXIT_ERROR;
+                break;
+            }
+            tokens.push(res.unwrap());
+        }
+
+        println!("{:?}", tokens);
+    }
+
+    return code;
+}
+
+/// Runs every stage short of executing and reports all diagnostics
+/// instead of stopping at the first one. Lexing is the only stage so far,
+/// followed by the lints; `--lint` turns on the ones that are off by
+/// default. `-A`, `-W` and `-D <lint>` set a lint to allow, warn or deny
+/// and `--deny-warnings`, or `deny-warnings` in the manifest,
+/// turns every warning into an error. With `--watch` it checks again
+/// whenever one of the files changes. `--print-module-resolution
+/// <module>` only shows where an import path is looked for.
+fn check(args: &[String]) -> i32 {
+    let mut watch = false;
+    let mut lint = false;
+    let mut config = LintConfig::default();
+    let mut paths = Vec::new();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        let level = match arg.as_str() {
+            "--watch" => {
+                watch = true;
+                continue;
+            },
+            "--deny-warnings" => {
+                config.deny_warnings = true;
+                continue;
+            },
+            "--lint" => {
+                lint = true;
+                continue;
+            },
+            "--print-module-resolution" => {
+                let Some(module) = args.next() else {
+                    eprintln!("Missing module path after '{}'", arg);
+                    return EXIT_USAGE;
+                };
+                return print_module_resolution(module);
+            },
+            "-A" => Level::Allow,
+            "-W" => Level::Warn,
+            "-D" => Level::Deny,
+            _ if is_option(arg) => return unknown_option(arg),
+            _ => {
+                paths.push(arg.clone());
+                continue;
+            },
+        };
+
+        let Some(name) = args.next() else {
+            eprintln!("Missing lint name after '{}'", arg);
+            return EXIT_USAGE;
+        };
+        if let Err(err) = config.set(name, level) {
+            eprintln!("{}", err);
+            return EXIT_USAGE;
+        }
+    }
+
+    if lint {
+        config.enable_all();
+    }
+
+    let dir = env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+    if let Ok(Some(manifest)) = Manifest::discover(&dir) {
+        config.deny_warnings |= manifest.compiler.deny_warnings;
+        if lock_dependencies(&manifest).is_none() {
+            return EXIT_ERROR;
+        }
     }
 
-    let string = std::fs::read_to_string(&args[1]).expect("Failed to read file");
+    let paths = match input_files(&paths) {
+        Some(paths) => paths,
+        None => return EXIT_ERROR,
+    };
+
+    if watch {
+        Watcher::new(paths.iter().map(PathBuf::from).collect()).run(|| {
+            if check_files(&paths, &config) == 0 {
+                println!("No errors found");
+            }
+        });
+    }
+
+    return check_files(&paths, &config);
+}
+
+fn check_files(paths: &[String], config: &LintConfig) -> i32 {
+    let source_map = match load_files(paths) {
+        Some(source_map) => source_map,
+        None => return EXIT_ERROR,
+    };
+
+    let mut diagnostics = Diagnostics::new(config.clone());
+
+    for file in source_map.files() {
+        diagnostics.check_file(file);
+    }
+
+    for diagnostic in diagnostics.diagnostics() {
+        eprintln!("{}\n", diagnostic);
+    }
+
+    let errors = diagnostics.count(Severity::Error);
+    let warnings = diagnostics.count(Severity::Warning);
+    match (errors, warnings) {
+        (0, 0) => {},
+        (_, 0) => eprintln!("Found {}", plural(errors, "error")),
+        (0, _) => eprintln!("Found {}", plural(warnings, "warning")),
+        _ => eprintln!("Found {} and {}", plural(errors, "error"), plural(warnings, "warning")),
+    }
+
+    return if errors > 0 { EXIT_ERROR } else { 0 };
+}
+
+/// Lists every file an import path is looked up in for the project in the
+/// current directory, in order: the one it resolves to, the ones that
+/// don't exist and the ones shadowed by an earlier match. Fails when the
+/// module isn't found.
+fn print_module_resolution(module: &str) -> i32 {
+    let dir = env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+    let manifest = match Manifest::discover(&dir) {
+        Ok(Some(manifest)) => manifest,
+        Ok(None) => {
+            eprintln!("No {} found", MANIFEST_NAME);
+            return EXIT_ERROR;
+        },
+        Err(err) => {
+            eprintln!("{}", err);
+            return EXIT_ERROR;
+        },
+    };
+
+    let packages = match lock_dependencies(&manifest) {
+        Some(packages) => packages,
+        None => return EXIT_ERROR,
+    };
+
+    let search_paths = env_module_paths();
+    let resolved = resolve_module(&manifest, &packages, &search_paths, module);
+
+    for candidate in module_candidates(&manifest, &packages, &search_paths, module) {
+        let status = if resolved.as_ref() == Some(&candidate) {
+            "found"
+        } else if candidate.is_file() {
+            "shadowed"
+        } else {
+            "missing"
+        };
+        println!("{:<8} {}", status, candidate.display());
+    }
+
+    if resolved.is_none() {
+        eprintln!("Module '{}' not found", module);
+        return EXIT_ERROR;
+    }
+
+    return 0;
+}
+
+/// Resolves the dependencies of the project and refreshes its lockfile if
+/// it no longer matches them. Prints the error on failure.
+fn lock_dependencies(manifest: &Manifest) -> Option<Vec<ResolvedPackage>> {
+    return match resolve_locked(manifest) {
+        Ok((packages, refreshed)) => {
+            if refreshed {
+                eprintln!("Updated {} to match {}", LOCKFILE_NAME, MANIFEST_NAME);
+            }
+            Some(packages)
+        },
+        Err(err) => {
+            eprintln!("{}", err);
+            None
+        },
+    };
+}
+
+fn plural(count: usize, word: &str) -> String {
+    return format!("{} {}{}", count, word, if count == 1 { "" } else { "s" });
+}
+
+/// Formats files in place, or with `--check` only reports the ones that
+/// are not formatted. Returns a nonzero exit code if any file failed to
+/// lex or, in check mode, would change.
+fn fmt(args: &[String]) -> i32 {
+    let check = args.iter().any(|a| a == "--check");
+    let paths: Vec<String> = args.iter()
+        .filter(|a| *a != "--check")
+        .cloned()
+        .collect();
+
+    if let Some(option) = paths.iter().find(|p| is_option(p)) {
+        return unknown_option(option);
+    }
+
+    let source_map = match load_files(&paths) {
+        Some(source_map) => source_map,
+        None => return EXIT_ERROR,
+    };
+
+    let mut code = 0;
+
+    for file in source_map.files() {
+        let formatted = match format_source(file) {
+            Ok(formatted) => formatted,
+            Err(err) => {
+                eprintln!("{}", err);
+                code = EXIT_ERROR;
+                continue;
+            }
+        };
+
+        if formatted == file.text {
+            continue;
+        }
+
+        if check {
+            println!("Would reformat: {}", file.name());
+            code = EXIT_ERROR;
+        } else if let Err(err) = std::fs::write(&file.path, formatted) {
+            eprintln!("Failed to write file '{}': {}", file.name(), err);
+            code = EXIT_ERROR;
+        }
+    }
+
+    return code;
+}
+
+/// Prints files with syntax highlighting, as ANSI colors by default or as
+/// an HTML fragment.
+fn highlight(args: &[String]) -> i32 {
+    let mut format = HighlightFormat::Ansi;
+    let mut paths = Vec::new();
+
+    for arg in args {
+        match arg.strip_prefix("--format=") {
+            Some("html") => format = HighlightFormat::Html,
+            Some("ansi") => format = HighlightFormat::Ansi,
+            Some(other) => {
+                eprintln!("Unknown highlight format '{}', expected 'html' or 'ansi'", other);
+                return EXIT_USAGE;
+            },
+            None if is_option(arg) => return unknown_option(arg),
+            None => paths.push(arg.clone()),
+        }
+    }
+
+    let source_map = match load_files(&paths) {
+        Some(source_map) => source_map,
+        None => return EXIT_ERROR,
+    };
+
+    for file in source_map.files() {
+        let lexed = LexedFile::new((**file).clone());
+        print!("{}", render(file, lexed.tokens(), format));
+    }
+
+    return 0;
+}
+
+/// Adds a path dependency to the project in the current directory and
+/// updates its lockfile.
+fn add(args: &[String]) -> i32 {
+    let (name, path) = match args {
+        [name, flag, path] if flag == "--path" => (name, path),
+        _ => {
+            eprintln!("Usage: lang3 add <name> --path <dir>");
+            return EXIT_USAGE;
+        },
+    };
+
+    let dir = env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+    let manifest = match Manifest::discover(&dir) {
+        Ok(Some(manifest)) => manifest,
+        Ok(None) => {
+            eprintln!("No {} found", MANIFEST_NAME);
+            return EXIT_ERROR;
+        },
+        Err(err) => {
+            eprintln!("{}", err);
+            return EXIT_ERROR;
+        },
+    };
+
+    return match add_dependency(&manifest, name, Path::new(path)) {
+        Ok(_) => {
+            println!("Added dependency '{}'", name);
+            0
+        },
+        Err(err) => {
+            eprintln!("{}", err);
+            EXIT_ERROR
+        },
+    };
+}
+
+/// Generates documentation for each file, printed or written to one file
+/// per module under `--out`.
+fn doc(args: &[String]) -> i32 {
+    let mut format = DocFormat::Markdown;
+    let mut out = None;
+    let mut paths = Vec::new();
+
+    for arg in args {
+        if let Some(dir) = arg.strip_prefix("--out=") {
+            out = Some(PathBuf::from(dir));
+            continue;
+        }
+
+        match arg.strip_prefix("--format=") {
+            Some("markdown") => format = DocFormat::Markdown,
+            Some("html") => format = DocFormat::Html,
+            Some(other) => {
+                eprintln!("Unknown doc format '{}', expected 'markdown' or 'html'", other);
+                return EXIT_USAGE;
+            },
+            None if is_option(arg) => return unknown_option(arg),
+            None => paths.push(arg.clone()),
+        }
+    }
+
+    let source_map = match load_files(&paths) {
+        Some(source_map) => source_map,
+        None => return EXIT_ERROR,
+    };
+
+    let extension = if format == DocFormat::Html { "html" } else { "md" };
+
+    for file in source_map.files() {
+        let name = file.path.file_stem().map_or(file.name(), |s| s.to_string_lossy().to_string());
+        let lexed = LexedFile::new((**file).clone());
+        let text = render_doc(&document(&name, file, lexed.tokens()), format);
 
-    let mut lexer =  Lexer::new(&string);
-    let mut tokens = Vec::<Token>::new();
+        let Some(dir) = &out else {
+            print!("{}", text);
+            continue;
+        };
 
-    while let Some(res) = lexer.next_token() {
-        if res.is_err() {
-            let err = res.err().unwrap();
-            println!("{}", err);
-            break;
+        let path = dir.join(&name).with_extension(extension);
+        if let Err(err) = std::fs::create_dir_all(dir).and_then(|_| std::fs::write(&path, text)) {
+            eprintln!("Failed to write file '{}': {}", path.display(), err);
+            return EXIT_ERROR;
         }
-        tokens.push(res.unwrap());
     }
 
-    println!("{:?}", tokens);
+    return 0;
 }
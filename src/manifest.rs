@@ -0,0 +1,227 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::Deserialize;
+
+pub const MANIFEST_NAME: &str = "lang3.toml";
+pub const SOURCE_EXTENSION: &str = "l3";
+
+/// Project manifest, `lang3.toml` at the project root:
+///
+/// ```toml
+/// [package]
+/// name = "app"
+/// entry = "src/main.l3"
+/// source-dirs = ["src"]
+/// module-paths = ["lib"]
+///
+/// [compiler]
+/// deny-warnings = true
+/// opt-level = 1
+///
+/// [dependencies]
+/// vendorlib = { path = "../vendorlib" }
+/// ```
+///
+/// Paths are relative to the directory holding the manifest.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Manifest {
+    pub package: Package,
+    #[serde(default)]
+    pub compiler: CompilerSettings,
+    #[serde(default)]
+    pub dependencies: BTreeMap<String, Dependency>,
+    #[serde(skip)]
+    pub root: PathBuf,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Package {
+    pub name: String,
+    #[serde(default = "default_entry")]
+    pub entry: PathBuf,
+    #[serde(default)]
+    pub source_dirs: Vec<PathBuf>,
+    #[serde(default)]
+    pub module_paths: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct CompilerSettings {
+    #[serde(default)]
+    pub deny_warnings: bool,
+    #[serde(default)]
+    pub opt_level: u8,
+}
+
+/// A package the project imports from, located by a path relative to the
+/// manifest. The dependency's own directory needs a manifest too.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Dependency {
+    pub path: PathBuf,
+}
+
+fn default_entry() -> PathBuf {
+    return PathBuf::from("main.l3");
+}
+
+#[derive(Debug)]
+pub struct ManifestError {
+    path: PathBuf,
+    msg: String,
+}
+
+impl ManifestError {
+    pub(crate) fn new(path: &Path, msg: impl ToString) -> Self {
+        return ManifestError { path: path.to_path_buf(), msg: msg.to_string() };
+    }
+}
+
+impl Error for ManifestError {}
+
+impl Display for ManifestError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        return write!(f, "Invalid manifest '{}': {}", self.path.display(), self.msg.trim_end());
+    }
+}
+
+impl Manifest {
+    pub fn parse(text: &str, root: &Path) -> Result<Self, ManifestError> {
+        let mut manifest: Manifest = toml::from_str(text)
+            .map_err(|err| ManifestError::new(&root.join(MANIFEST_NAME), err))?;
+        manifest.root = root.to_path_buf();
+        return Ok(manifest);
+    }
+
+    pub fn load(path: &Path) -> Result<Self, ManifestError> {
+        let text = fs::read_to_string(path).map_err(|err| ManifestError::new(path, err))?;
+        let root = path.parent().unwrap_or(Path::new("."));
+        return Manifest::parse(&text, root);
+    }
+
+    /// Looks for a manifest in `dir` and then in each of its parents.
+    pub fn discover(dir: &Path) -> Result<Option<Self>, ManifestError> {
+        for dir in dir.ancestors() {
+            let path = dir.join(MANIFEST_NAME);
+            if path.is_file() {
+                return Manifest::load(&path).map(Some);
+            }
+        }
+
+        return Ok(None);
+    }
+
+    pub fn entry(&self) -> PathBuf {
+        return self.root.join(&self.package.entry);
+    }
+
+    pub fn module_paths(&self) -> Vec<PathBuf> {
+        return self.package.module_paths.iter()
+            .map(|p| self.root.join(p))
+            .collect();
+    }
+
+    /// Directories modules of this package are looked up in: the source
+    /// directories, or the package root if there are none.
+    pub fn source_roots(&self) -> Vec<PathBuf> {
+        if self.package.source_dirs.is_empty() {
+            return vec![self.root.clone()];
+        }
+
+        return self.package.source_dirs.iter()
+            .map(|p| self.root.join(p))
+            .collect();
+    }
+
+    /// The entry point followed by every other source file found in the
+    /// source directories, in a stable order.
+    pub fn source_files(&self) -> Vec<PathBuf> {
+        let entry = self.entry();
+        let mut files = Vec::new();
+
+        for dir in &self.package.source_dirs {
+            collect_sources(&self.root.join(dir), &mut files);
+        }
+
+        files.sort();
+        files.retain(|f| *f != entry);
+        files.insert(0, entry);
+
+        return files;
+    }
+}
+
+fn collect_sources(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else { return };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_sources(&path, files);
+        } else if path.extension().is_some_and(|e| e == SOURCE_EXTENSION) {
+            files.push(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod manifest_tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use super::{Manifest, MANIFEST_NAME};
+
+    #[test]
+    fn test_parse() {
+        // given
+        let text = "[package]\nname = \"app\"\nsource-dirs = [\"src\"]\n\n[compiler]\ndeny-warnings = true\n";
+
+        // when
+        let manifest = Manifest::parse(text, Path::new("/project")).unwrap();
+
+        // then
+        assert_eq!(manifest.package.name, "app");
+        assert_eq!(manifest.entry(), PathBuf::from("/project/main.l3"));
+        assert!(manifest.compiler.deny_warnings);
+        assert_eq!(manifest.compiler.opt_level, 0);
+    }
+
+    #[test]
+    fn test_parse_unknown_key() {
+        // given
+        let text = "[package]\nname = \"app\"\nentrypoint = \"a.l3\"\n";
+
+        // when
+        let err = Manifest::parse(text, Path::new("/project")).unwrap_err();
+
+        // then
+        assert!(err.to_string().starts_with("Invalid manifest '/project/lang3.toml'"));
+        assert!(err.to_string().contains("entrypoint"));
+    }
+
+    #[test]
+    fn test_discover() {
+        // given
+        let root = std::env::temp_dir().join(format!("lang3_manifest_{}", std::process::id()));
+        fs::create_dir_all(root.join("src/util")).unwrap();
+        fs::write(root.join(MANIFEST_NAME), "[package]\nname = \"app\"\nentry = \"src/main.l3\"\nsource-dirs = [\"src\"]\n").unwrap();
+        fs::write(root.join("src/main.l3"), "").unwrap();
+        fs::write(root.join("src/a.l3"), "").unwrap();
+        fs::write(root.join("src/util/b.l3"), "").unwrap();
+        fs::write(root.join("src/notes.txt"), "").unwrap();
+
+        // when
+        let manifest = Manifest::discover(&root.join("src/util")).unwrap().unwrap();
+        let files = manifest.source_files();
+        fs::remove_dir_all(&root).unwrap();
+
+        // then
+        assert_eq!(manifest.root, root);
+        assert_eq!(files, [root.join("src/main.l3"), root.join("src/a.l3"), root.join("src/util/b.l3")]);
+    }
+}
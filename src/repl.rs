@@ -0,0 +1,347 @@
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+use rustyline::completion::{Completer, Pair};
+use rustyline::error::ReadlineError;
+use rustyline::highlight::{Highlighter, MatchingBracketHighlighter};
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::{ValidationContext, ValidationResult, Validator};
+use rustyline::{Context, Editor, Helper};
+use crate::lexer::Lexer;
+use crate::source::{FileId, SourceMap};
+use crate::token::{Token, TokenKind};
+
+const PROMPT: &str = ">> ";
+const HISTORY_FILE: &str = ".lang3_history";
+const COMMANDS: [&str; 3] = [":help", ":quit", ":type"];
+
+const HELP: &str = "\
+Commands:
+  :help          Show this message
+  :quit, :q      Exit the REPL
+  :type <expr>   Show the type of <expr> (needs a type checker)
+
+Input is read until all braces, brackets and parentheses are balanced.
+Tab completes keywords and identifiers from earlier inputs, and history is
+kept in ~/.lang3_history.";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplAction {
+    Continue,
+    Quit,
+}
+
+/// Each input replaces the text of a single scratch file in the REPL's
+/// `SourceMap`, so a long session doesn't hold on to every line typed.
+pub struct Repl {
+    source_map: SourceMap,
+    scratch: FileId,
+}
+
+impl Repl {
+    pub fn new() -> Self {
+        let mut source_map = SourceMap::new();
+        let scratch = source_map.add_file(PathBuf::from("<repl>"), String::new());
+        return Repl { source_map, scratch };
+    }
+
+    pub fn run(&mut self) {
+        let mut editor = match Editor::<ReplHelper, DefaultHistory>::new() {
+            Ok(editor) => editor,
+            Err(err) => {
+                eprintln!("Failed to start line editor: {}", err);
+                return;
+            }
+        };
+        editor.set_helper(Some(ReplHelper::new()));
+
+        let history = history_path();
+        if let Some(path) = &history {
+            // a missing history file just means this is the first session
+            let _ = editor.load_history(path);
+        }
+
+        loop {
+            let input = match editor.readline(PROMPT) {
+                Ok(input) => input,
+                Err(ReadlineError::Interrupted) => continue,
+                Err(ReadlineError::Eof) => break,
+                Err(err) => {
+                    eprintln!("{}", err);
+                    break;
+                }
+            };
+
+            if input.trim().is_empty() {
+                continue;
+            }
+
+            let _ = editor.add_history_entry(input.as_str());
+
+            if input.trim_start().starts_with(':') {
+                if self.handle_command(input.trim()) == ReplAction::Quit {
+                    break;
+                }
+                continue;
+            }
+
+            if let Some(tokens) = self.eval(input) {
+                if let Some(helper) = editor.helper_mut() {
+                    helper.add_identifiers(&tokens);
+                }
+            }
+        }
+
+        if let Some(path) = &history {
+            if let Err(err) = editor.save_history(path) {
+                eprintln!("Failed to save history to '{}': {}", path.display(), err);
+            }
+        }
+    }
+
+    pub fn handle_command(&mut self, command: &str) -> ReplAction {
+        let (name, rest) = command.split_once(char::is_whitespace)
+            .unwrap_or((command, ""));
+
+        match name {
+            ":help" | ":h" => println!("{}", HELP),
+            ":quit" | ":q" => return ReplAction::Quit,
+            ":type" | ":t" if rest.trim().is_empty() => eprintln!("Usage: :type <expr>"),
+            ":type" | ":t" => eprintln!("Not implemented: there is no type checker to infer the type of an expression yet"),
+            _ => eprintln!("Unknown command '{}', try :help", name),
+        }
+
+        return ReplAction::Continue;
+    }
+
+    pub fn eval(&mut self, input: String) -> Option<Vec<Token>> {
+        let tokens = self.lex(input)?;
+        let lexemes: Vec<String> = tokens.iter()
+            .map(Token::to_string)
+            .collect();
+        println!("[{}]", lexemes.join(", "));
+
+        return Some(tokens);
+    }
+
+    fn lex(&mut self, input: String) -> Option<Vec<Token>> {
+        self.source_map.set_text(self.scratch, input);
+        let file = self.source_map.get(self.scratch);
+
+        let mut lexer = Lexer::from_source_file(file);
+        let mut tokens = Vec::new();
+
+        while let Some(res) = lexer.next_token() {
+            match res {
+                Ok(token) => tokens.push(token),
+                Err(err) => {
+                    eprintln!("{}", err);
+                    return None;
+                }
+            }
+        }
+
+        return Some(tokens);
+    }
+}
+
+impl Default for Repl {
+    fn default() -> Self {
+        return Repl::new();
+    }
+}
+
+/// Line editor hooks: keyword and identifier completion, multi-line input
+/// and highlighting of the bracket matching the one under the cursor.
+pub struct ReplHelper {
+    identifiers: BTreeSet<String>,
+    brackets: MatchingBracketHighlighter,
+}
+
+impl ReplHelper {
+    pub fn new() -> Self {
+        return ReplHelper {
+            identifiers: BTreeSet::new(),
+            brackets: MatchingBracketHighlighter::new(),
+        };
+    }
+
+    /// Remembers the identifiers of an evaluated input so later lines can
+    /// complete them.
+    pub fn add_identifiers(&mut self, tokens: &[Token]) {
+        tokens.iter()
+            .filter(|t| t.kind == TokenKind::Identifier)
+            .for_each(|t| {
+                self.identifiers.insert(t.lexeme.clone());
+            });
+    }
+
+    pub fn candidates(&self, prefix: &str) -> Vec<String> {
+        let mut candidates: Vec<String> = TokenKind::keywords()
+            .chain(COMMANDS)
+            .filter(|c| c.starts_with(prefix))
+            .map(str::to_string)
+            .collect();
+        candidates.extend(self.identifiers.iter()
+            .filter(|i| i.starts_with(prefix))
+            .cloned());
+        candidates.sort();
+        candidates.dedup();
+        return candidates;
+    }
+}
+
+impl Default for ReplHelper {
+    fn default() -> Self {
+        return ReplHelper::new();
+    }
+}
+
+impl Helper for ReplHelper {}
+
+impl Completer for ReplHelper {
+    type Candidate = Pair;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let start = line[..pos]
+            .rfind(|c: char| !(c.is_alphanumeric() || c == '_' || c == '$' || c == ':'))
+            .map_or(0, |i| i + 1);
+        let prefix = &line[start..pos];
+
+        if prefix.is_empty() {
+            return Ok((start, Vec::new()));
+        }
+
+        let pairs = self.candidates(prefix)
+            .into_iter()
+            .map(|c| Pair { display: c.clone(), replacement: c })
+            .collect();
+
+        return Ok((start, pairs));
+    }
+}
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ReplHelper {
+    fn highlight<'l>(&self, line: &'l str, pos: usize) -> Cow<'l, str> {
+        return self.brackets.highlight(line, pos);
+    }
+
+    fn highlight_char(&self, line: &str, pos: usize, forced: bool) -> bool {
+        return self.brackets.highlight_char(line, pos, forced);
+    }
+}
+
+impl Validator for ReplHelper {
+    fn validate(&self, ctx: &mut ValidationContext) -> rustyline::Result<ValidationResult> {
+        let input = ctx.input().to_string();
+
+        if input.trim_start().starts_with(':') || is_complete(&input) {
+            return Ok(ValidationResult::Valid(None));
+        }
+
+        return Ok(ValidationResult::Incomplete);
+    }
+}
+
+fn history_path() -> Option<PathBuf> {
+    return std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(|home| PathBuf::from(home).join(HISTORY_FILE));
+}
+
+/// Returns false while `input` still has unclosed delimiters, strings or
+/// block comments, meaning the REPL should keep reading lines. A stray
+/// closing delimiter can't be fixed by more input, so it is skipped
+/// instead of cancelling out a later opening one.
+pub fn is_complete(input: &String) -> bool {
+    let mut lexer = Lexer::new(input);
+    let mut depth: usize = 0;
+
+    while let Some(res) = lexer.next_token() {
+        match res {
+            Ok(token) => match token.kind {
+                TokenKind::LeftBrace
+                | TokenKind::LeftParenthesis
+                | TokenKind::LeftBracket => depth += 1,
+                TokenKind::RightBrace
+                | TokenKind::RightParenthesis
+                | TokenKind::RightBracket => depth = depth.saturating_sub(1),
+                _ => {}
+            },
+            Err(err) => return !err.is_unterminated(),
+        }
+    }
+
+    return depth == 0;
+}
+
+#[cfg(test)]
+mod repl_tests {
+    use super::{is_complete, Repl, ReplAction, ReplHelper};
+
+    #[test]
+    fn test_is_complete() {
+        let inputs = [
+            ("let x = 1;", true),
+            ("fn main() {", false),
+            ("fn main() {\n}", true),
+            ("print(foo(1, [2, 3]", false),
+            ("\"unterminated", false),
+            ("/* open comment", false),
+            ("}", true),
+            ("} {", false),
+            (") fn f() {\n}", true),
+        ];
+
+        for (input, expected) in inputs {
+            assert_eq!(is_complete(&input.to_string()), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_handle_command() {
+        // given
+        let mut repl = Repl::new();
+
+        // then
+        assert_eq!(repl.handle_command(":help"), ReplAction::Continue);
+        assert_eq!(repl.handle_command(":type let x"), ReplAction::Continue);
+        assert_eq!(repl.handle_command(":quit"), ReplAction::Quit);
+        assert_eq!(repl.handle_command(":q"), ReplAction::Quit);
+    }
+
+    #[test]
+    fn test_completion_candidates() {
+        // given
+        let mut helper = ReplHelper::new();
+        let mut repl = Repl::new();
+        let tokens = repl.eval("let counter = 1; let count = 2;".to_string()).unwrap();
+
+        // when
+        helper.add_identifiers(&tokens);
+
+        // then
+        assert_eq!(helper.candidates("co"), ["const", "continue", "count", "counter"]);
+        assert_eq!(helper.candidates(":q"), [":quit"]);
+        assert!(helper.candidates("xyz").is_empty());
+    }
+
+    #[test]
+    fn test_inputs_reuse_scratch_file() {
+        // given
+        let mut repl = Repl::new();
+
+        // when
+        repl.eval("let a = 1;".to_string());
+        repl.eval("let b = 2;".to_string());
+
+        // then
+        assert_eq!(repl.source_map.files().count(), 1);
+        assert_eq!(repl.source_map.get(repl.scratch).text, "let b = 2;");
+    }
+}
@@ -1,15 +1,284 @@
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FileId(usize);
+
+impl FileId {
+    /// Id of files made with `SourceFile::anonymous`, which belong to no
+    /// `SourceMap` and so can't be mistaken for one of its files.
+    pub const ANONYMOUS: FileId = FileId(usize::MAX);
+
+    pub fn index(&self) -> usize {
+        return self.0;
+    }
+}
+
+/// Byte range `start..end` within a source file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        return Span { start, end };
+    }
+
+    pub fn len(&self) -> usize {
+        return self.end - self.start;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.start == self.end;
+    }
+
+    /// Smallest span covering both spans.
+    pub fn join(self, other: Span) -> Span {
+        return Span::new(self.start.min(other.start), self.end.max(other.end));
+    }
+
+    /// The gap from the end of this span to the start of `other`, which
+    /// comes after it.
+    pub fn between(self, other: Span) -> Span {
+        return Span::new(self.end, other.start.max(self.end));
+    }
+
+    /// Empty span at the start of this one.
+    pub fn shrink_to_start(self) -> Span {
+        return Span::new(self.start, self.start);
+    }
+
+    /// Empty span at the end of this one.
+    pub fn shrink_to_end(self) -> Span {
+        return Span::new(self.end, self.end);
+    }
+
+    pub fn contains(self, other: Span) -> bool {
+        return self.start <= other.start && other.end <= self.end;
+    }
+}
+
+/// A value together with the source range it came from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Spanned<T> {
+    pub node: T,
+    pub span: Span,
+}
+
+impl<T> Spanned<T> {
+    pub fn new(node: T, span: Span) -> Self {
+        return Spanned { node, span };
+    }
+
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> Spanned<U> {
+        return Spanned::new(f(self.node), self.span);
+    }
+
+    pub fn as_ref(&self) -> Spanned<&T> {
+        return Spanned::new(&self.node, self.span);
+    }
+}
+
+/// Zero-based line and column. Columns count characters, not bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineColumn {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl LineColumn {
+    pub fn new(line: usize, column: usize) -> Self {
+        return LineColumn { line, column };
+    }
+}
+
+/// Byte offset of the start of every line, built once per file so going
+/// between offsets and line/column positions is a binary search instead of
+/// a scan from the top of the file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineIndex {
+    line_starts: Vec<usize>,
+}
+
+impl LineIndex {
+    pub fn new(text: &str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        return LineIndex { line_starts };
+    }
+
+    /// Updates the index after `span` of the old text was replaced by
+    /// `text`. Lines before the edit are kept as they are, the ones inside
+    /// it are replaced and the ones after it are shifted.
+    pub fn apply_edit(&mut self, span: Span, text: &str) {
+        let first = self.line_starts.partition_point(|start| *start <= span.start);
+        let last = self.line_starts.partition_point(|start| *start <= span.end);
+
+        for start in &mut self.line_starts[last..] {
+            *start = *start + text.len() - span.len();
+        }
+
+        let inserted = text.match_indices('\n').map(|(i, _)| span.start + i + 1);
+        self.line_starts.splice(first..last, inserted);
+    }
+
+    pub fn line_count(&self) -> usize {
+        return self.line_starts.len();
+    }
+
+    /// Byte range of a zero-based line without its line break.
+    pub fn line_span(&self, text: &str, line: usize) -> Option<Span> {
+        let start = *self.line_starts.get(line)?;
+        let end = self.line_starts.get(line + 1).map_or(text.len(), |next| next - 1);
+        let end = if text[start..end].ends_with('\r') { end - 1 } else { end };
+
+        return Some(Span::new(start, end));
+    }
+
+    /// Position of a byte offset. Offsets past the end or inside a
+    /// character move back to the nearest character boundary.
+    pub fn offset_to_position(&self, text: &str, offset: usize) -> LineColumn {
+        let mut offset = offset.min(text.len());
+        while !text.is_char_boundary(offset) {
+            offset -= 1;
+        }
+
+        let line = self.line_starts.partition_point(|start| *start <= offset) - 1;
+        let column = text[self.line_starts[line]..offset].chars().count();
+
+        return LineColumn::new(line, column);
+    }
+
+    /// Byte offset of a position. Columns past the end of the line mean the
+    /// end of the line; lines past the end of the file have no offset.
+    pub fn position_to_offset(&self, text: &str, position: LineColumn) -> Option<usize> {
+        let span = self.line_span(text, position.line)?;
+        let offset = text[span.start..span.end].char_indices()
+            .nth(position.column)
+            .map_or(span.end, |(i, _)| span.start + i);
+
+        return Some(offset);
+    }
+}
+
 #[derive(Debug, Clone)]
-pub struct SourceCodeLocation {
+pub struct SourceFile {
+    pub id: FileId,
+    pub path: PathBuf,
     pub text: String,
+    line_index: LineIndex,
+}
+
+impl SourceFile {
+    pub fn new(id: FileId, path: PathBuf, text: String) -> Self {
+        let line_index = LineIndex::new(&text);
+        return SourceFile {
+            id,
+            path,
+            text,
+            line_index,
+        };
+    }
+
+    /// Creates a file that is not backed by anything on disk, e.g. a string
+    /// passed directly to the lexer.
+    pub fn anonymous(text: String) -> Self {
+        return SourceFile::new(FileId::ANONYMOUS, PathBuf::from("<anonymous>"), text);
+    }
+
+    pub fn name(&self) -> String {
+        return self.path.display().to_string();
+    }
+
+    pub fn slice(&self, span: Span) -> &str {
+        return &self.text[span.start..span.end];
+    }
+
+    pub fn line_index(&self) -> &LineIndex {
+        return &self.line_index;
+    }
+
+    /// Text of the line numbered `row`, counting from 1 like token lines,
+    /// without its line break. Empty past the end of the file.
+    pub fn line(&self, row: usize) -> &str {
+        let span = row.checked_sub(1).and_then(|line| self.line_index.line_span(&self.text, line));
+        return span.map_or("", |span| self.slice(span));
+    }
+
+    pub fn offset_to_position(&self, offset: usize) -> LineColumn {
+        return self.line_index.offset_to_position(&self.text, offset);
+    }
+
+    pub fn position_to_offset(&self, position: LineColumn) -> Option<usize> {
+        return self.line_index.position_to_offset(&self.text, position);
+    }
+
+    /// Replaces the text in `span` with `text`.
+    pub fn apply_edit(&mut self, span: Span, text: &str) {
+        self.text.replace_range(span.start..span.end, text);
+        self.line_index.apply_edit(span, text);
+    }
+}
+
+/// Owns every source file loaded during a compilation. Files are never
+/// removed, so a `FileId` handed out by the map stays valid for its lifetime.
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    files: Vec<Rc<SourceFile>>,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        return SourceMap::default();
+    }
+
+    pub fn add_file(&mut self, path: PathBuf, text: String) -> FileId {
+        let id = FileId(self.files.len());
+        self.files.push(Rc::new(SourceFile::new(id, path, text)));
+        return id;
+    }
+
+    pub fn load_file(&mut self, path: &Path) -> std::io::Result<FileId> {
+        if let Some(file) = self.files.iter().find(|f| f.path == path) {
+            return Ok(file.id);
+        }
+
+        let text = std::fs::read_to_string(path)?;
+        return Ok(self.add_file(path.to_path_buf(), text));
+    }
+
+    /// Replaces the whole text of a file, keeping its id and path. Earlier
+    /// locations keep the old text alive until they are dropped.
+    pub fn set_text(&mut self, id: FileId, text: String) {
+        let file = &mut self.files[id.0];
+        *file = Rc::new(SourceFile::new(id, file.path.clone(), text));
+    }
+
+    pub fn get(&self, id: FileId) -> &Rc<SourceFile> {
+        return &self.files[id.0];
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = &Rc<SourceFile>> {
+        return self.files.iter();
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SourceCodeLocation {
+    pub file: Rc<SourceFile>,
     pub line: usize,
     pub start_char: usize,
     pub end_char: usize,
 }
 
 impl SourceCodeLocation {
-    pub fn new(text: String, line: usize, start_char: usize, end_char: usize) -> Self {
+    pub fn new(file: Rc<SourceFile>, line: usize, start_char: usize, end_char: usize) -> Self {
         return SourceCodeLocation {
-            text,
+            file,
             line,
             start_char,
             end_char,
@@ -17,3 +286,106 @@ impl SourceCodeLocation {
     }
 }
 
+impl Display for SourceCodeLocation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        return write!(f, "{}:{}:{}", self.file.name(), self.line, self.start_char);
+    }
+}
+
+#[cfg(test)]
+mod source_tests {
+    use std::path::PathBuf;
+    use std::rc::Rc;
+    use super::{FileId, LineColumn, LineIndex, SourceCodeLocation, SourceFile, SourceMap, Span, Spanned};
+
+    #[test]
+    fn test_line_index() {
+        // given
+        let file = SourceFile::anonymous("let a;\r\nlet ść = 1;\n\nend".to_string());
+
+        // when
+        let position = file.offset_to_position(file.text.find('=').unwrap());
+
+        // then
+        assert_eq!(file.line_index().line_count(), 4);
+        assert_eq!((file.line(1), file.line(2), file.line(3), file.line(4), file.line(5)), ("let a;", "let ść = 1;", "", "end", ""));
+        assert_eq!(position, LineColumn::new(1, 7));
+        assert_eq!(file.position_to_offset(position), file.text.find('='));
+        assert_eq!(file.offset_to_position(file.text.len()), LineColumn::new(3, 3));
+        assert_eq!(file.offset_to_position(13), LineColumn::new(1, 4));
+        assert_eq!(file.position_to_offset(LineColumn::new(0, 99)), Some(6));
+        assert_eq!(file.position_to_offset(LineColumn::new(4, 0)), None);
+    }
+
+    #[test]
+    fn test_line_index_edits() {
+        // given
+        let mut file = SourceFile::anonymous("a\nbc\n\ndef\n".to_string());
+        let edits: [(usize, usize, &str); 6] = [
+            (1, 1, "x"),
+            (0, 0, "\n\n"),
+            (4, 9, ""),
+            (2, 3, "1\n2\n3"),
+            (0, 4, "\r\n"),
+            (0, 0, ""),
+        ];
+
+        for (start, end, text) in edits {
+            // when
+            file.apply_edit(Span::new(start, end), text);
+
+            // then
+            assert_eq!(*file.line_index(), LineIndex::new(&file.text), "after replacing {}..{} with {:?}", start, end, text);
+        }
+    }
+
+    #[test]
+    fn test_span_helpers() {
+        // given
+        let name = Span::new(4, 7);
+        let value = Span::new(10, 12);
+
+        // when
+        let statement = name.join(value);
+
+        // then
+        assert_eq!(statement, Span::new(4, 12));
+        assert_eq!(value.join(name), statement);
+        assert_eq!(name.between(value), Span::new(7, 10));
+        assert!(value.between(name).is_empty());
+        assert_eq!(statement.shrink_to_start(), Span::new(4, 4));
+        assert_eq!(statement.shrink_to_end(), Span::new(12, 12));
+        assert!(statement.contains(value) && !value.contains(statement));
+        assert_eq!(Spanned::new("x", name).map(str::len), Spanned::new(1, name));
+    }
+
+    #[test]
+    fn test_add_file_assigns_ids() {
+        // given
+        let mut map = SourceMap::new();
+
+        // when
+        let first = map.add_file(PathBuf::from("main.l3"), "a".to_string());
+        let second = map.add_file(PathBuf::from("lib/module.l3"), "b".to_string());
+
+        // then
+        assert_ne!(first, second);
+        assert_ne!(first, FileId::ANONYMOUS);
+        assert_eq!(SourceFile::anonymous(String::new()).id, FileId::ANONYMOUS);
+        assert_eq!(map.get(first).text, "a");
+        assert_eq!(map.get(second).name(), "lib/module.l3");
+    }
+
+    #[test]
+    fn test_location_display() {
+        // given
+        let mut map = SourceMap::new();
+        let id = map.add_file(PathBuf::from("path/to/module.l3"), "let x;".to_string());
+
+        // when
+        let location = SourceCodeLocation::new(Rc::clone(map.get(id)), 3, 7, 9);
+
+        // then
+        assert_eq!(location.to_string(), "path/to/module.l3:3:7");
+    }
+}
@@ -0,0 +1,269 @@
+use crate::interner::{Interner, Symbol};
+use crate::source::Span;
+use crate::token::{Token, TokenKind};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DeclarationKind {
+    Function,
+    Parameter,
+    Variable,
+    Constant,
+    Class,
+    Struct,
+    Interface,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Declaration {
+    pub name: Symbol,
+    pub kind: DeclarationKind,
+    pub line: usize,
+    pub start_char: usize,
+    pub end_char: usize,
+    pub span: Span,
+}
+
+impl Declaration {
+    fn new(names: &mut Interner, token: &Token, kind: DeclarationKind) -> Self {
+        return Declaration {
+            name: names.intern(&token.lexeme),
+            kind,
+            line: token.line,
+            start_char: token.start_char,
+            end_char: token.end_char,
+            span: token.span,
+        };
+    }
+}
+
+/// The declarations of one token stream. Their names are interned in a
+/// table of their own that is dropped together with them.
+#[derive(Debug, Default)]
+pub struct Declarations {
+    names: Interner,
+    declarations: Vec<Declaration>,
+}
+
+impl Declarations {
+    pub fn iter(&self) -> std::slice::Iter<'_, Declaration> {
+        return self.declarations.iter();
+    }
+
+    pub fn name(&self, declaration: &Declaration) -> &str {
+        return self.names.resolve(declaration.name);
+    }
+
+    /// Picks the declaration a use of `name` at byte `offset` most likely
+    /// refers to: the closest one before it, or the first one after it for
+    /// functions and classes used before their definition.
+    pub fn find(&self, name: &str, offset: usize) -> Option<&Declaration> {
+        // a name that was never interned isn't declared anywhere
+        let name = self.names.get(name)?;
+        let mut candidates = self.declarations.iter().filter(|d| d.name == name);
+
+        return candidates.clone()
+            .rfind(|d| d.span.start <= offset)
+            .or_else(|| candidates.next());
+    }
+}
+
+impl<'a> IntoIterator for &'a Declarations {
+    type Item = &'a Declaration;
+    type IntoIter = std::slice::Iter<'a, Declaration>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        return self.iter();
+    }
+}
+
+/// Finds the names introduced by `fn`, `let`, `const`, `with`, `class`,
+/// `struct` and `interface`, plus function parameters, including the names bound by
+/// `{x, y}` and `[a, b]` parameter patterns. Property accessors, `get
+/// name()` and `set name(v)`, count as functions. There is no resolver yet,
+/// so this works on the token stream alone and knows nothing about scopes.
+pub fn declarations(tokens: &[Token]) -> Declarations {
+    let tokens: Vec<&Token> = tokens.iter()
+        .filter(|t| !t.kind.is_trivia())
+        .collect();
+    let kind_at = |i: usize| tokens.get(i).map(|t| t.kind);
+
+    let mut names = Interner::new();
+    let mut declarations = Vec::new();
+
+    for (i, token) in tokens.iter().enumerate() {
+        let kind = match token.kind {
+            TokenKind::Let => DeclarationKind::Variable,
+            TokenKind::Const => DeclarationKind::Constant,
+            TokenKind::With if kind_at(i + 2) == Some(TokenKind::Equal) => DeclarationKind::Variable,
+            TokenKind::Class => DeclarationKind::Class,
+            TokenKind::Struct => DeclarationKind::Struct,
+            TokenKind::Interface => DeclarationKind::Interface,
+            TokenKind::Fn => DeclarationKind::Function,
+            TokenKind::Identifier if is_accessor(&tokens, i) => DeclarationKind::Function,
+            _ => continue,
+        };
+
+        let mut next = i + 1;
+        if kind == DeclarationKind::Function && kind_at(next) == Some(TokenKind::Star) {
+            next += 1;
+        }
+
+        if kind_at(next) == Some(TokenKind::Identifier) {
+            declarations.push(Declaration::new(&mut names, tokens[next], kind));
+            next += 1;
+        }
+
+        if kind != DeclarationKind::Function || kind_at(next) != Some(TokenKind::LeftParenthesis) {
+            continue;
+        }
+
+        // for each open bracket, whether it is the parameter list or a
+        // destructuring pattern in it rather than a default value
+        let mut patterns: Vec<bool> = Vec::new();
+        for (j, param) in tokens.iter().enumerate().skip(next) {
+            let binding = patterns.last() == Some(&true) && matches!(kind_at(j - 1), Some(
+                TokenKind::LeftParenthesis | TokenKind::LeftBracket | TokenKind::LeftBrace
+                | TokenKind::Comma | TokenKind::DotDotDot | TokenKind::Colon
+            ));
+
+            match param.kind {
+                TokenKind::LeftParenthesis | TokenKind::LeftBracket | TokenKind::LeftBrace => patterns.push(patterns.is_empty() || binding),
+                TokenKind::RightParenthesis | TokenKind::RightBracket | TokenKind::RightBrace => {
+                    patterns.pop();
+                },
+                // `{key: name}` binds `name`, not `key`
+                TokenKind::Identifier if binding && kind_at(j + 1) != Some(TokenKind::Colon) => {
+                    declarations.push(Declaration::new(&mut names, param, DeclarationKind::Parameter));
+                },
+                _ => {},
+            }
+
+            if patterns.is_empty() {
+                break;
+            }
+        }
+    }
+
+    return Declarations { names, declarations };
+}
+
+/// Whether the identifier at `index` is the `get` or `set` of a property
+/// accessor: directly inside a class, struct or interface body and with a
+/// body of its own after the parameter list. They are keywords only there,
+/// so `map.get(key)` and a call like `set x(1);` still work.
+fn is_accessor(tokens: &[&Token], index: usize) -> bool {
+    let header = matches!(tokens[index].lexeme.as_str(), "get" | "set")
+        && tokens.get(index + 1).is_some_and(|t| t.kind == TokenKind::Identifier)
+        && tokens.get(index + 2).is_some_and(|t| t.kind == TokenKind::LeftParenthesis);
+    if !header {
+        return false;
+    }
+
+    let mut depth = 0;
+    let parameters_end = tokens.iter().enumerate().skip(index + 2).find(|(_, t)| {
+        match t.kind {
+            TokenKind::LeftParenthesis | TokenKind::LeftBracket | TokenKind::LeftBrace => depth += 1,
+            TokenKind::RightParenthesis | TokenKind::RightBracket | TokenKind::RightBrace => depth -= 1,
+            _ => {},
+        }
+        return depth == 0;
+    });
+    let has_body = parameters_end.and_then(|(i, _)| tokens.get(i + 1)).is_some_and(|t| t.kind == TokenKind::LeftBrace);
+
+    return has_body && enclosing_brace(tokens, index).is_some_and(|brace| is_type_body(tokens, brace));
+}
+
+/// Index of the `{` of the innermost brace group holding the token at
+/// `index`, if it is inside one and not in a nested `(...)` or `[...]`.
+fn enclosing_brace(tokens: &[&Token], index: usize) -> Option<usize> {
+    let mut depth = 0;
+
+    for i in (0..index).rev() {
+        match tokens[i].kind {
+            TokenKind::RightParenthesis | TokenKind::RightBracket | TokenKind::RightBrace => depth += 1,
+            TokenKind::LeftParenthesis | TokenKind::LeftBracket if depth == 0 => return None,
+            TokenKind::LeftBrace if depth == 0 => return Some(i),
+            TokenKind::LeftParenthesis | TokenKind::LeftBracket | TokenKind::LeftBrace => depth -= 1,
+            _ => {},
+        }
+    }
+
+    return None;
+}
+
+/// Whether the `{` at `brace` opens the body of a class, struct or
+/// interface, i.e. one of those keywords comes after the previous `;`,
+/// `{` or `}`.
+fn is_type_body(tokens: &[&Token], brace: usize) -> bool {
+    return tokens[..brace].iter().rev()
+        .take_while(|t| !matches!(t.kind, TokenKind::Semicolon | TokenKind::LeftBrace | TokenKind::RightBrace))
+        .any(|t| matches!(t.kind, TokenKind::Class | TokenKind::Struct | TokenKind::Interface));
+}
+
+#[cfg(test)]
+mod symbols_tests {
+    use crate::lexer::Lexer;
+    use super::{declarations, DeclarationKind};
+
+    fn tokens(code: &str) -> Vec<crate::token::Token> {
+        let code = code.to_string();
+        let mut lexer = Lexer::new(&code);
+        let mut tokens = Vec::new();
+        while let Some(token) = lexer.next_token() {
+            tokens.push(token.unwrap());
+        }
+        return tokens;
+    }
+
+    #[test]
+    fn test_declarations() {
+        // given
+        let tokens = tokens("class A { get area() {} set name(v) {} } obj.get(k); set x(1); { get y() {} } class B { m(get, z) {} } with f = open() {} with lock {} struct P { x, y } interface I { fn f(); } fn* gen(a, ...rest) { let x = f(a); } const C = 1; fn draw({x: px, y}, [r, ...g], b = [z]) {}");
+
+        // when
+        let declarations = declarations(&tokens);
+
+        // then
+        let found: Vec<(&str, DeclarationKind)> = declarations.iter()
+            .map(|d| (declarations.name(d), d.kind))
+            .collect();
+        assert_eq!(found, [
+            ("A", DeclarationKind::Class),
+            ("area", DeclarationKind::Function),
+            ("name", DeclarationKind::Function),
+            ("v", DeclarationKind::Parameter),
+            ("B", DeclarationKind::Class),
+            ("f", DeclarationKind::Variable),
+            ("P", DeclarationKind::Struct),
+            ("I", DeclarationKind::Interface),
+            ("f", DeclarationKind::Function),
+            ("gen", DeclarationKind::Function),
+            ("a", DeclarationKind::Parameter),
+            ("rest", DeclarationKind::Parameter),
+            ("x", DeclarationKind::Variable),
+            ("C", DeclarationKind::Constant),
+            ("draw", DeclarationKind::Function),
+            ("px", DeclarationKind::Parameter),
+            ("y", DeclarationKind::Parameter),
+            ("r", DeclarationKind::Parameter),
+            ("g", DeclarationKind::Parameter),
+            ("b", DeclarationKind::Parameter),
+        ]);
+    }
+
+    #[test]
+    fn test_find_declaration() {
+        // given
+        let code = "main(); let x = 1; let x = 2; fn main() { x; }";
+        let declarations = declarations(&tokens(code));
+
+        // when
+        let main = declarations.find("main", 0).unwrap();
+        let x = declarations.find("x", code.rfind('x').unwrap()).unwrap();
+
+        // then
+        assert_eq!(main.kind, DeclarationKind::Function);
+        assert_eq!(x.span.start, code.find("x = 2").unwrap());
+        assert!(declarations.find("y", 0).is_none());
+    }
+}
@@ -2,15 +2,28 @@ use std::fmt::{Debug, Display, Formatter};
 use std::iter::{Iterator};
 use std::str::FromStr;
 use phf::{phf_map, Map};
+use crate::source::{FileId, Span};
 
 
 #[derive(Debug, Clone)]
 pub struct Token {
     pub kind: TokenKind,
+    pub file: FileId,
     pub lexeme: String,
     pub line: usize,
     pub start_char: usize,
     pub end_char: usize,
+    pub span: Span,
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        return match self.kind {
+            TokenKind::String => write!(f, "\"{}\"", self.lexeme.escape_debug()),
+            TokenKind::Char => write!(f, "'{}'", self.lexeme.escape_debug()),
+            _ => write!(f, "{}", self.lexeme),
+        };
+    }
 }
 
 #[allow(dead_code)]
@@ -19,12 +32,19 @@ pub enum TokenKind {
     Invalid,
     Super,                     // super
     Class,                     // class
+    Struct,                    // struct
+    Interface,                 // interface
     This,                      // this
     While,                     // while
+    Do,                        // do
     If,                        // if
     Else,                      // else
     For,                       // for
     Foreach,                   // foreach
+    Match,                     // match
+    Switch,                    // switch
+    Case,                      // case
+    Default,                   // default
     In,                        // in
     Continue,                  // continue
     Break,                     // break
@@ -34,16 +54,28 @@ pub enum TokenKind {
     Import,                    // import
     Include,                   // include
     As,                        // as
+    Is,                        // is
     Fn,                        // fn
+    Async,                     // async
+    Await,                     // await
     Return,                    // return
+    Yield,                     // yield
+    Try,                       // try
+    Catch,                     // catch
+    Finally,                   // finally
+    Throw,                     // throw
+    Defer,                     // defer
+    With,                      // with
     Let,                       // let
     Const,                     // const
+    Static,                    // static
     Print,                     // @temporary
     FatArrow,                  // =>
     ThinArrow,                 // ->
     Equal,                     // =
     QuestionmarkQuestionmark,  // ??
     Questionmark,              // ?
+    QuestionmarkDot,           // ?.
     Colon,                     // :
     Plus,                      // +
     Minus,                     // -
@@ -66,6 +98,7 @@ pub enum TokenKind {
     LessLess,                  // <<
     GreaterGreater,            // >>
     Tilde,                     // ~
+    At,                        // @
     PlusPlus,                  // ++
     MinusMinus,                // --
     MinusEqual,                // -=
@@ -74,6 +107,8 @@ pub enum TokenKind {
     SlashEqual,                // /=
     Dot,                       // .
     DotDot,                    // ..
+    DotDotDot,                 // ...
+    DotDotEqual,               // ..=
     Comma,                     // ,
     Semicolon,                 // ;
     LeftParenthesis,           // (
@@ -87,6 +122,9 @@ pub enum TokenKind {
     Char,
     Integer,
     Float,
+    Whitespace,
+    LineComment,
+    BlockComment,
 }
 
 impl Display for TokenKind {
@@ -95,12 +133,19 @@ impl Display for TokenKind {
             TokenKind::Invalid => "<invalid>",
             TokenKind::Super => "super",
             TokenKind::Class => "class",
+            TokenKind::Struct => "struct",
+            TokenKind::Interface => "interface",
             TokenKind::This => "this",
             TokenKind::While => "while",
+            TokenKind::Do => "do",
             TokenKind::If => "if",
             TokenKind::Else => "else",
             TokenKind::For => "for",
             TokenKind::Foreach => "foreach",
+            TokenKind::Match => "match",
+            TokenKind::Switch => "switch",
+            TokenKind::Case => "case",
+            TokenKind::Default => "default",
             TokenKind::In => "in",
             TokenKind::Continue => "continue",
             TokenKind::Break => "break",
@@ -110,16 +155,28 @@ impl Display for TokenKind {
             TokenKind::Import => "import",
             TokenKind::Include => "include",
             TokenKind::As => "as",
+            TokenKind::Is => "is",
             TokenKind::Fn => "fn",
+            TokenKind::Async => "async",
+            TokenKind::Await => "await",
             TokenKind::Return => "return",
+            TokenKind::Yield => "yield",
+            TokenKind::Try => "try",
+            TokenKind::Catch => "catch",
+            TokenKind::Finally => "finally",
+            TokenKind::Throw => "throw",
+            TokenKind::Defer => "defer",
+            TokenKind::With => "with",
             TokenKind::Let => "let",
             TokenKind::Const => "const",
+            TokenKind::Static => "static",
             TokenKind::Print => "@temporary",
             TokenKind::FatArrow => "=>",
             TokenKind::ThinArrow => "->",
             TokenKind::Equal => "=",
             TokenKind::QuestionmarkQuestionmark => "??",
             TokenKind::Questionmark => "?",
+            TokenKind::QuestionmarkDot => "?.",
             TokenKind::Colon => ":",
             TokenKind::Plus => "+",
             TokenKind::Minus => "-",
@@ -142,6 +199,7 @@ impl Display for TokenKind {
             TokenKind::LessLess => "<<",
             TokenKind::GreaterGreater => ">>",
             TokenKind::Tilde => "~",
+            TokenKind::At => "@",
             TokenKind::PlusPlus => "++",
             TokenKind::MinusMinus => "--",
             TokenKind::MinusEqual => "-=",
@@ -150,6 +208,8 @@ impl Display for TokenKind {
             TokenKind::SlashEqual => "/=",
             TokenKind::Dot => ".",
             TokenKind::DotDot => "..",
+            TokenKind::DotDotDot => "...",
+            TokenKind::DotDotEqual => "..=",
             TokenKind::Comma => ",",
             TokenKind::Semicolon => ";",
             TokenKind::LeftParenthesis => "(",
@@ -163,6 +223,9 @@ impl Display for TokenKind {
             TokenKind::Char => "<char>",
             TokenKind::Integer => "<integer>",
             TokenKind::Float => "<float>",
+            TokenKind::Whitespace => "<whitespace>",
+            TokenKind::LineComment => "<line comment>",
+            TokenKind::BlockComment => "<block comment>",
         };
 
         write!(f, "{}", str)
@@ -172,12 +235,19 @@ impl Display for TokenKind {
 const TOKEN_KIND_MAP: Map<&'static str, TokenKind> = phf_map! {
     "super" => TokenKind::Super,
     "class" => TokenKind::Class,
+    "struct" => TokenKind::Struct,
+    "interface" => TokenKind::Interface,
     "this" => TokenKind::This,
     "while" => TokenKind::While,
+    "do" => TokenKind::Do,
     "if" => TokenKind::If,
     "else" => TokenKind::Else,
     "for" => TokenKind::For,
     "foreach" => TokenKind::Foreach,
+    "match" => TokenKind::Match,
+    "switch" => TokenKind::Switch,
+    "case" => TokenKind::Case,
+    "default" => TokenKind::Default,
     "in" => TokenKind::In,
     "continue" => TokenKind::Continue,
     "break" => TokenKind::Break,
@@ -187,16 +257,28 @@ const TOKEN_KIND_MAP: Map<&'static str, TokenKind> = phf_map! {
     "import" => TokenKind::Import,
     "include" => TokenKind::Include,
     "as" => TokenKind::As,
+    "is" => TokenKind::Is,
     "fn" => TokenKind::Fn,
+    "async" => TokenKind::Async,
+    "await" => TokenKind::Await,
     "return" => TokenKind::Return,
+    "yield" => TokenKind::Yield,
+    "try" => TokenKind::Try,
+    "catch" => TokenKind::Catch,
+    "finally" => TokenKind::Finally,
+    "throw" => TokenKind::Throw,
+    "defer" => TokenKind::Defer,
+    "with" => TokenKind::With,
     "let" => TokenKind::Let,
     "const" => TokenKind::Const,
+    "static" => TokenKind::Static,
     "print" => TokenKind::Print,
     "=>" => TokenKind::FatArrow,
     "->" => TokenKind::ThinArrow,
     "=" => TokenKind::Equal,
     "??" => TokenKind::QuestionmarkQuestionmark,
     "?" => TokenKind::Questionmark,
+    "?." => TokenKind::QuestionmarkDot,
     ":" => TokenKind::Colon,
     "+" => TokenKind::Plus,
     "-" => TokenKind::Minus,
@@ -219,6 +301,7 @@ const TOKEN_KIND_MAP: Map<&'static str, TokenKind> = phf_map! {
     "<<" => TokenKind::LessLess,
     ">>" => TokenKind::GreaterGreater,
     "~" => TokenKind::Tilde,
+    "@" => TokenKind::At,
     "++" => TokenKind::PlusPlus,
     "--" => TokenKind::MinusMinus,
     "-=" => TokenKind::MinusEqual,
@@ -227,6 +310,8 @@ const TOKEN_KIND_MAP: Map<&'static str, TokenKind> = phf_map! {
     "/=" => TokenKind::SlashEqual,
     "." => TokenKind::Dot,
     ".." => TokenKind::DotDot,
+    "..." => TokenKind::DotDotDot,
+    "..=" => TokenKind::DotDotEqual,
     "," => TokenKind::Comma,
     ";" => TokenKind::Semicolon,
     "(" => TokenKind::LeftParenthesis,
@@ -246,14 +331,25 @@ impl FromStr for TokenKind {
 }
 
 impl TokenKind {
-    pub fn to_str(&self) -> &'static str {
+    pub fn keywords() -> impl Iterator<Item = &'static str> {
+        return TOKEN_KIND_MAP.keys()
+            .copied()
+            .filter(|k| k.chars().all(char::is_alphabetic));
+    }
+
+    /// Whitespace and comments, only produced by a lexer with trivia enabled.
+    pub fn is_trivia(self) -> bool {
+        return matches!(self, TokenKind::Whitespace | TokenKind::LineComment | TokenKind::BlockComment);
+    }
+
+    pub fn to_str(self) -> &'static str {
         return TOKEN_KIND_MAP.entries()
-            .find(|&v| v.1 == self)
+            .find(|&v| *v.1 == self)
             .unwrap()
             .0;
     }
 
-    pub fn parse_operator(c: char, c1: Option<char>) -> Option<Self> {
+    pub fn parse_operator(c: char, c1: Option<char>, c2: Option<char>) -> Option<Self> {
         if c == '!' {
             return if c1 == Option::from('=') {
                 Some(TokenKind::BangEqual)
@@ -307,13 +403,17 @@ impl TokenKind {
             } else if c1 == Option::from('=') {
                 Some(TokenKind::MinusEqual)
             } else if c1 == Option::from('>') {
-                Some(TokenKind::FatArrow)
+                Some(TokenKind::ThinArrow)
             } else {
                 Some(TokenKind::Minus)
             };
         }
         if c == '.' {
-            return if c1 == Option::from('.') {
+            return if c1 == Option::from('.') && c2 == Option::from('.') {
+                Some(TokenKind::DotDotDot)
+            } else if c1 == Option::from('.') && c2 == Option::from('=') {
+                Some(TokenKind::DotDotEqual)
+            } else if c1 == Option::from('.') {
                 Some(TokenKind::DotDot)
             } else {
                 Some(TokenKind::Dot)
@@ -335,6 +435,8 @@ impl TokenKind {
         if c == '<' {
             return if c1 == Option::from('=') {
                 Some(TokenKind::LessEqual)
+            } else if c1 == Option::from('<') {
+                Some(TokenKind::LessLess)
             } else {
                 Some(TokenKind::Less)
             };
@@ -351,25 +453,37 @@ impl TokenKind {
         if c == '>' {
             return if c1 == Option::from('=') {
                 Some(TokenKind::GreaterEqual)
+            } else if c1 == Option::from('>') {
+                Some(TokenKind::GreaterGreater)
             } else {
                 Some(TokenKind::Greater)
             };
         }
 
         if c == '?' {
+            // `a ?.5 : b` is a conditional with a number, not a null-safe access
             return if c1 == Option::from('?') {
                 Some(TokenKind::QuestionmarkQuestionmark)
+            } else if c1 == Option::from('.') && !c2.is_some_and(|c| c.is_ascii_digit()) {
+                Some(TokenKind::QuestionmarkDot)
             } else {
                 Some(TokenKind::Questionmark)
             };
         }
 
+        if c == '@' {
+            return Some(TokenKind::At);
+        }
+
         if c == '[' {
             return Some(TokenKind::LeftBracket);
         }
         if c == ']' {
             return Some(TokenKind::RightBracket);
         }
+        if c == '^' {
+            return Some(TokenKind::Caret);
+        }
         if c == '{' {
             return Some(TokenKind::LeftBrace);
         }
@@ -383,7 +497,27 @@ impl TokenKind {
         if c == '}' {
             return Some(TokenKind::RightBrace);
         }
+        if c == '~' {
+            return Some(TokenKind::Tilde);
+        }
 
         return None;
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod token_tests {
+    use std::str::FromStr;
+    use super::{TokenKind, TOKEN_KIND_MAP};
+
+    #[test]
+    fn test_kind_str_round_trip() {
+        for (text, kind) in TOKEN_KIND_MAP.entries() {
+            // when
+            let parsed = TokenKind::from_str(kind.to_str());
+
+            // then
+            assert_eq!(parsed, Ok(*kind), "{}", text);
+            assert_eq!(kind.to_str(), *text);
+        }
+    }
+}
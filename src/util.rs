@@ -1,4 +1,7 @@
 use colored::Colorize;
+use crate::highlight::{colorize, highlight};
+use crate::incremental::LexedFile;
+use crate::source::{SourceCodeLocation, SourceFile};
 
 pub fn print_prefix(line_no: &str) {
     line_no.chars().for_each(|_| eprint!(" "));
@@ -14,32 +17,48 @@ pub fn print_underline(start_char: usize, end_char: usize) {
         eprint!(" ");
     }
     for _ in start_char..end_char {
-        eprint!("{}", format!("^").bright_red());
+        eprint!("{}", "^".bright_red());
     }
 }
 
+/// Prints a source line syntax highlighted, with the characters the error
+/// points at in red. The line is lexed on its own, so tokens continuing
+/// from earlier lines, like the rest of a block comment, are not
+/// recognised as such.
 pub fn print_error_line(line: &str, start_char: usize, end_char: usize) {
-    for (i, c) in line.chars().enumerate() {
-        if i < start_char - 1 {
-            eprint!("{}", c);
-        } else if i < end_char {
-            eprint!("{}", format!("{}", c).bright_red());
-        } else {
-            eprint!("{}", c);
+    let lexed = LexedFile::new(SourceFile::anonymous(line.to_string()));
+    let kinds = highlight(lexed.tokens());
+
+    let end_char = end_char.max(start_char + 1);
+
+    for (token, kind) in lexed.tokens().iter().zip(kinds) {
+        let chars: Vec<char> = lexed.file().slice(token.span).chars().collect();
+        let error_start = start_char.saturating_sub(token.start_char).min(chars.len());
+        let error_end = end_char.saturating_sub(token.start_char).clamp(error_start, chars.len());
+
+        let before: String = chars[..error_start].iter().collect();
+        let error: String = chars[error_start..error_end].iter().collect();
+        let after: String = chars[error_end..].iter().collect();
+
+        for (text, colored) in [(&before, colorize(&before, kind)), (&error, error.bright_red()), (&after, colorize(&after, kind))] {
+            if !text.is_empty() {
+                eprint!("{}", colored);
+            }
         }
     }
 }
 
-pub fn get_error_line(text: &str, row: usize) -> String {
-    let mut lines = text.lines();
-    let line = lines.nth(row - 1 as usize).unwrap_or("");
-    let line = line.replace('\t', " ");
-    line.to_owned()
+pub fn get_error_line(file: &SourceFile, row: usize) -> String {
+    return file.line(row).replace('\t', " ");
+}
+
+pub fn print_file_location(location: &SourceCodeLocation) {
+    eprintln!("{} {}", "-->".blue(), location);
 }
 
-pub fn print_location(text: &String, row: usize, start_char: usize, end_char: usize) {
+pub fn print_location(file: &SourceFile, row: usize, start_char: usize, end_char: usize) {
     let line_no = (row).to_string();
-    let line = get_error_line(text, row);
+    let line = get_error_line(file, row);
 
     print_prefix(&line_no);
     eprintln!();
@@ -0,0 +1,85 @@
+use std::fs;
+use std::io::{stdout, Write};
+use std::path::{Path, PathBuf};
+use std::thread::sleep;
+use std::time::{Duration, SystemTime};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+const DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// Polls a set of files for changes by their modification time. Polling
+/// keeps this dependency free and is plenty for the handful of files a
+/// script consists of.
+pub struct Watcher {
+    paths: Vec<PathBuf>,
+    modified: Vec<Option<SystemTime>>,
+}
+
+impl Watcher {
+    pub fn new(paths: Vec<PathBuf>) -> Self {
+        let modified = paths.iter().map(|p| modified(p)).collect();
+        return Watcher { paths, modified };
+    }
+
+    /// Returns whether any file was modified, created or removed since the
+    /// last call.
+    pub fn changed(&mut self) -> bool {
+        let modified: Vec<Option<SystemTime>> = self.paths.iter().map(|p| modified(p)).collect();
+        let changed = modified != self.modified;
+        self.modified = modified;
+        return changed;
+    }
+
+    /// Calls `run` once and then again after every change, clearing the
+    /// screen in between. Editors often write a file in several steps, so
+    /// a rebuild only starts once the files stopped changing.
+    pub fn run(&mut self, mut run: impl FnMut()) -> ! {
+        loop {
+            print!("\x1b[2J\x1b[H");
+            let _ = stdout().flush();
+            run();
+
+            while !self.changed() {
+                sleep(POLL_INTERVAL);
+            }
+
+            sleep(DEBOUNCE);
+            while self.changed() {
+                sleep(DEBOUNCE);
+            }
+        }
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    return fs::metadata(path).and_then(|m| m.modified()).ok();
+}
+
+#[cfg(test)]
+mod watch_tests {
+    use std::fs;
+    use std::time::{Duration, SystemTime};
+    use super::Watcher;
+
+    #[test]
+    fn test_changed() {
+        // given
+        let path = std::env::temp_dir().join(format!("lang3_watch_{}.l3", std::process::id()));
+        fs::write(&path, "let a = 1;").unwrap();
+        let mut watcher = Watcher::new(vec![path.clone()]);
+
+        // when
+        let unchanged = watcher.changed();
+        fs::File::options().write(true).open(&path).unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(10)).unwrap();
+        let modified = watcher.changed();
+        fs::remove_file(&path).unwrap();
+        let removed = watcher.changed();
+
+        // then
+        assert!(!unchanged);
+        assert!(modified);
+        assert!(removed);
+        assert!(!watcher.changed());
+    }
+}
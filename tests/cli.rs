@@ -0,0 +1,41 @@
+#![allow(clippy::needless_return)]
+
+//! Runs the `lang3` binary to check how it treats malformed command lines.
+
+use std::process::Command;
+
+const EXIT_ERROR: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+
+fn exit_code(args: &[&str]) -> i32 {
+    let output = Command::new(env!("CARGO_BIN_EXE_lang3"))
+        .args(args)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .unwrap();
+    return output.status.code().unwrap();
+}
+
+#[test]
+fn test_usage_errors() {
+    // given
+    let cases: [(&[&str], i32); 9] = [
+        (&[], EXIT_USAGE),
+        (&["chekc"], EXIT_USAGE),
+        (&["--verbose"], EXIT_USAGE),
+        (&["tests/cases/lints.l3", "--tokens"], EXIT_USAGE),
+        (&["check", "--wacth", "tests/cases/lints.l3"], EXIT_USAGE),
+        (&["fmt", "--chek", "tests/cases/lints.l3"], EXIT_USAGE),
+        (&["highlight", "--html", "tests/cases/lints.l3"], EXIT_USAGE),
+        (&["missing.l3"], EXIT_ERROR),
+        (&["fmt", "--check", "tests/cases/lints.l3"], 0),
+    ];
+
+    for (args, expected) in cases {
+        // when
+        let code = exit_code(args);
+
+        // then
+        assert_eq!(code, expected, "lang3 {}", args.join(" "));
+    }
+}
@@ -0,0 +1,101 @@
+#![allow(clippy::needless_return)]
+
+//! Golden tests: every `tests/cases/<name>.l3` is run through the pipeline
+//! and the results are compared with the snapshots next to it,
+//! `<name>.tokens` for the token stream and `<name>.diagnostics` for what
+//! `lang3 check --lint` reports. Run with `UPDATE_GOLDEN=1` to write the
+//! snapshots from the current output instead, then review the diff.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use lang3::diagnostics::{Diagnostics, LintConfig, Severity};
+use lang3::lexer::Lexer;
+use lang3::source::SourceMap;
+
+fn cases_dir() -> PathBuf {
+    return Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/cases");
+}
+
+fn dump_tokens(code: &String) -> String {
+    let mut lexer = Lexer::new(code);
+    let mut out = String::new();
+    let mut position = lexer.position();
+
+    while let Some(res) = lexer.next_token() {
+        match res {
+            Ok(token) => out.push_str(&format!("{}:{} {:?} {:?}\n", token.line, token.start_char, token.kind, token.lexeme)),
+            Err(err) => {
+                out.push_str(&format!("error: {}\n", err.message()));
+                if lexer.position() == position {
+                    break;
+                }
+            },
+        }
+        position = lexer.position();
+    }
+
+    return out;
+}
+
+fn dump_diagnostics(path: &Path) -> String {
+    let mut source_map = SourceMap::new();
+    let id = source_map.load_file(path).unwrap();
+    let mut config = LintConfig::default();
+    config.enable_all();
+    let mut diagnostics = Diagnostics::new(config);
+
+    diagnostics.check_file(&Rc::clone(source_map.get(id)));
+
+    return diagnostics.diagnostics().iter()
+        .map(|d| {
+            let severity = if d.severity == Severity::Error { "error" } else { "warning" };
+            let lint = d.lint.map(|l| format!("[{}]", l)).unwrap_or_default();
+            let location = d.location.as_ref().map(|l| format!("{}:{}: ", l.line, l.start_char)).unwrap_or_default();
+            return format!("{}{}{}: {}\n", location, severity, lint, d.message);
+        })
+        .collect();
+}
+
+/// Compares `actual` with the snapshot at `path`, or writes it when
+/// updating. Returns a description of the mismatch.
+fn compare(path: &Path, actual: &str, update: bool) -> Option<String> {
+    if update {
+        fs::write(path, actual).unwrap();
+        return None;
+    }
+
+    let expected = fs::read_to_string(path).unwrap_or_default();
+    if expected == actual {
+        return None;
+    }
+
+    let line = expected.lines().zip(actual.lines())
+        .position(|(e, a)| e != a)
+        .unwrap_or(expected.lines().count().min(actual.lines().count()));
+    return Some(format!("{} differs at line {}:\n  expected: {}\n  actual:   {}",
+                        path.display(), line + 1,
+                        expected.lines().nth(line).unwrap_or("<end>"),
+                        actual.lines().nth(line).unwrap_or("<end>")));
+}
+
+#[test]
+fn golden_cases() {
+    let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+    let mut cases: Vec<PathBuf> = fs::read_dir(cases_dir()).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|e| e == "l3"))
+        .collect();
+    cases.sort();
+    assert!(!cases.is_empty());
+
+    let mut failures = Vec::new();
+    for case in &cases {
+        let code = fs::read_to_string(case).unwrap();
+
+        failures.extend(compare(&case.with_extension("tokens"), &dump_tokens(&code), update));
+        failures.extend(compare(&case.with_extension("diagnostics"), &dump_diagnostics(case), update));
+    }
+
+    assert!(failures.is_empty(), "{}\n\nRun with UPDATE_GOLDEN=1 to accept the new output.", failures.join("\n\n"));
+}